@@ -17,7 +17,7 @@ async fn main() -> EResult<()> {
         println!(
             "Post {id} created at {created_at} by {owner} [{image_url}]",
             id = post.id(),
-            created_at = post.created_at(),
+            created_at = post.created_at_raw(),
             owner = post.owner(),
             image_url = post.image_url(),
         );
@@ -2,539 +2,3318 @@
 //!
 //! Use the associated functions at the root module and `RequestBuilder`s to send requests.
 
-use crate::{Client, Error};
-use hyper::body::Buf;
-use serde::Deserialize;
+use crate::query::{Meta, Query};
+use crate::runtime::Instant;
+use crate::{AuthDetails, Client, Error, ResponseFormat, UnavailableKind};
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use hyper::body::Bytes;
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::convert::{AsRef, Into};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-// marker trait for API types
-trait ApiQuery: serde::de::DeserializeOwned {}
+// API response types, readable from both JSON and XML
+pub(crate) trait ApiQuery: serde::de::DeserializeOwned {
+    // element name of the listed items, which doubles as their key in JSON
+    #[cfg(feature = "xml")]
+    const ITEM: &'static str;
 
-const API_BASE: &'static str = "https://gelbooru.com/index.php?page=dapi&q=index&json=1";
+    // endpoints that only respond in XML, whatever the client's format
+    #[cfg(feature = "xml")]
+    const XML_ONLY: bool = false;
 
-type QueryStrings<'a> = HashMap<&'a str, String>;
+    // and those that only respond in JSON
+    #[cfg(feature = "xml")]
+    const JSON_ONLY: bool = false;
 
-#[derive(Deserialize, Debug)]
+    // endpoint, relative to the client's base URL
+    const PATH: &'static str = API_PATH;
+
+    fn from_json(body: &[u8]) -> Result<Self, Error> {
+        serde_json::from_slice(body).map_err(Error::JsonDeserialize)
+    }
+
+    // see `Client::keep_raw_responses`
+    fn keep_raw(&mut self, _body: Bytes) {}
+
+    // base URL of the site that responded, for items linking back to it
+    fn set_site(&mut self, _base_url: &Arc<str>) {}
+
+    #[cfg(feature = "xml")]
+    fn from_xml(body: &[u8]) -> Result<Self, Error> {
+        let value = crate::xml::list_to_json(body, Self::ITEM)?;
+        serde_json::from_value(value).map_err(Error::JsonDeserialize)
+    }
+}
+
+// relative to the client's base URL
+const API_PATH: &str = "/index.php?page=dapi&q=index";
+const AUTOCOMPLETE_PATH: &str = "/index.php?page=autocomplete2&type=tag_query&limit=10";
+const POOL_PATH: &str = "/index.php?page=pool&s=show";
+
+pub use crate::stream::{PostStream, TagStream};
+
+// maximum amount of tags the Tags endpoint returns for a single request
+pub(crate) const TAG_BATCH_SIZE: usize = 100;
+
+// Gelbooru refuses to page any further into a search
+const MAX_PAGINATION_OFFSET: usize = 20_000;
+// most posts the API returns per request
+const MAX_LIMIT: usize = 100;
+// tags marking animated posts
+const ANIMATED_TAGS: [&str; 3] = ["animated", "video", "webm"];
+
+// ordered, so the same request always builds the same URL
+type QueryStrings<'a> = BTreeMap<&'a str, String>;
+
+// per-request settings overriding those of the client
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RequestOptions {
+    // `Some(None)` sends the request unauthenticated
+    pub(crate) auth: Option<Option<AuthDetails>>,
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) cancel: Option<CancelSignal>,
+}
+
+// future completing once the request should be cancelled, shared by every request it's set on
+#[derive(Clone)]
+pub(crate) struct CancelSignal(futures::future::Shared<futures::future::BoxFuture<'static, ()>>);
+
+impl CancelSignal {
+    fn new<F: std::future::Future<Output = ()> + Send + 'static>(signal: F) -> Self {
+        use futures::FutureExt;
+
+        Self(signal.boxed().shared())
+    }
+}
+
+impl std::fmt::Debug for CancelSignal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancelSignal").finish_non_exhaustive()
+    }
+}
+
+// setters of the per-request options, shared by every builder sending requests through
+// `query_api`, so their docs live in one place
+macro_rules! request_options {
+    () => {
+        /// Deadline for the whole operation: every
+        /// [retry](../struct.Client.html#method.retries) and every page of aggregate fetches
+        /// such as [`fetch_concurrent`](struct.PostsRequestBuilder.html#method.fetch_concurrent)
+        /// and [`into_stream`](struct.PostsRequestBuilder.html#method.into_stream).
+        ///
+        /// Requests still running by then fail with
+        /// [`Error::DeadlineExceeded`](../enum.Error.html#variant.DeadlineExceeded). Aggregate
+        /// fetches keep what they gathered: `fetch_concurrent` returns the posts collected so
+        /// far, marked with `deadline_exceeded`, while streams end with the error after their
+        /// last post.
+        ///
+        /// ## Example
+        /// ```rust
+        /// # use gelbooru_api::{Client, Error, FetchOrder, posts};
+        /// # async fn example() -> Result<(), Error> {
+        /// # let client = Client::public();
+        /// use std::time::{Duration, Instant};
+        ///
+        /// let collected = posts()
+        ///     .tags(&["hatsune_miku"])
+        ///     .deadline(Instant::now() + Duration::from_secs(30))
+        ///     .fetch_concurrent(&client, 4, FetchOrder::Ordered)
+        ///     .await?;
+        ///
+        /// if collected.deadline_exceeded {
+        ///     println!("ran out of time after {} posts", collected.posts.len());
+        /// }
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn deadline(mut self, deadline: Instant) -> Self {
+            self.options.deadline = Some(deadline);
+            self
+        }
+
+        /// Longest to wait for each request to the API, retries included, failing with
+        /// [`Error::Timeout`](../enum.Error.html#variant.Timeout) if it takes longer.
+        ///
+        /// Unlike the client's
+        /// [request timeout](../struct.ClientBuilder.html#method.request_timeout), this only
+        /// applies to requests made from this builder, so interactive searches can be held to a
+        /// tighter budget than background work sharing the client. Unlike a
+        /// [deadline](#method.deadline), every page of aggregate fetches gets the full timeout.
+        ///
+        /// ## Example
+        /// ```rust
+        /// # use gelbooru_api::{Client, Error, posts};
+        /// # async fn example() -> Result<(), Error> {
+        /// # let client = Client::public();
+        /// use std::time::Duration;
+        ///
+        /// let results = posts()
+        ///     .tags(&["hatsune_miku"])
+        ///     .timeout(Duration::from_secs(3))
+        ///     .send(&client)
+        ///     .await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn timeout(mut self, timeout: Duration) -> Self {
+            self.options.timeout = Some(timeout);
+            self
+        }
+
+        /// Stop once `signal` completes: requests in flight are aborted and fail with
+        /// [`Error::Cancelled`](../enum.Error.html#variant.Cancelled), and no further pages are
+        /// requested. Like with a [deadline](#method.deadline), aggregate fetches keep what they
+        /// gathered, marked as `cancelled`.
+        ///
+        /// Any future works as a signal, whatever the runtime, such as a `futures` oneshot
+        /// channel or tokio-util's `CancellationToken::cancelled_owned`. Dropping a request's
+        /// future aborts it just as well, so requests are also safe to race with
+        /// `futures::select!` or `tokio::select!`.
+        ///
+        /// ## Example
+        /// ```rust
+        /// # use gelbooru_api::{Client, Error, posts};
+        /// # async fn example() -> Result<(), Error> {
+        /// # let client = Client::public();
+        /// use futures::channel::oneshot;
+        ///
+        /// let (stop, stopped) = oneshot::channel::<()>();
+        /// let crawl = posts()
+        ///     .tags(&["hatsune_miku"])
+        ///     .cancel_on(async move {
+        ///         let _ = stopped.await;
+        ///     })
+        ///     .fetch_all(&client, 10_000);
+        ///
+        /// // elsewhere, such as when the user hits stop
+        /// let _ = stop.send(());
+        ///
+        /// let collected = crawl.await?;
+        /// assert!(collected.cancelled);
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn cancel_on<F>(mut self, signal: F) -> Self
+        where
+            F: std::future::Future<Output = ()> + Send + 'static,
+        {
+            self.options.cancel = Some(CancelSignal::new(signal));
+            self
+        }
+
+        /// Credentials to send this request with, overriding those of the client.
+        /// `None` sends the request unauthenticated.
+        ///
+        /// Lets a bot serving several users route each request under the user's own API key
+        /// while sharing a single client.
+        ///
+        /// ## Example
+        /// ```rust
+        /// # use gelbooru_api::{AuthDetails, Client, Error, posts};
+        /// # async fn example() -> Result<(), Error> {
+        /// # let client = Client::public();
+        /// let user = AuthDetails::from_query_string("&api_key=0123456789abcdef&user_id=1234")?;
+        ///
+        /// posts()
+        ///     .tags(&["hatsune_miku"])
+        ///     .auth(Some(user))
+        ///     .send(&client)
+        ///     .await?;
+        /// # Ok(())
+        /// # }
+        /// ```
+        pub fn auth(mut self, auth: Option<AuthDetails>) -> Self {
+            self.options.auth = Some(auth);
+            self
+        }
+    };
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[non_exhaustive]
 pub struct Attributes {
+    #[serde(deserialize_with = "crate::de::number")]
     pub limit: usize,
+    #[serde(deserialize_with = "crate::de::number")]
     pub offset: usize,
+    #[serde(deserialize_with = "crate::de::number")]
     pub count: usize,
 }
 
-#[derive(Deserialize, Debug)]
+/// Page of posts.
+///
+/// Serializes to the API's own JSON format, so saved pages can be read back like responses.
+#[derive(Deserialize, Serialize, Debug)]
+#[non_exhaustive]
 pub struct PostQuery {
     #[serde(rename = "@attributes")]
     pub attributes: Attributes,
-    #[serde(rename = "post", default = "Vec::new")]
+    #[serde(
+        rename = "post",
+        default = "Vec::new",
+        deserialize_with = "crate::de::one_or_many"
+    )]
     pub posts: Vec<Post>,
+    // request that produced the page, for `next_page`
+    #[serde(skip)]
+    pub(crate) request: Option<PostsRequestBuilder<'static>>,
+    #[serde(skip)]
+    pub(crate) blacklisted: Vec<Post>,
+    // listed bare by a Gelbooru 0.2 site, so the attributes are made up
+    #[serde(skip)]
+    pub(crate) legacy: bool,
+    #[serde(skip)]
+    pub(crate) raw: Option<Bytes>,
 }
 
-#[derive(Deserialize, Debug)]
+/// Page of tags.
+///
+/// Serializes to the API's own JSON format, so saved pages can be read back like responses.
+#[derive(Deserialize, Serialize, Debug)]
+#[non_exhaustive]
 pub struct TagQuery {
     #[serde(rename = "@attributes")]
     pub attributes: Attributes,
-    #[serde(rename = "tag", default = "Vec::new")]
+    #[serde(
+        rename = "tag",
+        default = "Vec::new",
+        deserialize_with = "crate::de::one_or_many"
+    )]
     pub tags: Vec<Tag>,
+    #[serde(skip)]
+    pub(crate) raw: Option<Bytes>,
+}
+
+/// Result of [`PostsRequestBuilder::enriched`](struct.PostsRequestBuilder.html#method.enriched).
+#[derive(Clone, Debug)]
+pub struct EnrichedPostQuery {
+    pub attributes: Attributes,
+    pub posts: Vec<EnrichedPost>,
+}
+
+/// Saved position in a search, see [`PostQuery::cursor`](struct.PostQuery.html#method.cursor).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PageCursor {
+    /// Tags of the search, as sent.
+    pub query: String,
+    /// Amount of posts per page.
+    pub limit: usize,
+    /// How a limit above 100 posts is handled.
+    #[serde(default)]
+    pub limit_policy: LimitPolicy,
+    /// Offset of the next post.
+    pub offset: usize,
+    /// Tags whose posts are dropped from the results, see
+    /// [`PostsRequestBuilder::blacklist`](struct.PostsRequestBuilder.html#method.blacklist).
+    #[serde(default)]
+    pub blacklist: Vec<String>,
+    /// Time of the changes searched for, see
+    /// [`PostsRequestBuilder::changed_at`](struct.PostsRequestBuilder.html#method.changed_at).
+    #[serde(default)]
+    pub cid: Option<u64>,
+}
+
+/// Posts gathered from several pages of a search.
+#[derive(Clone, Debug)]
+pub struct CollectedPosts {
+    /// Amount of posts matching the search, as reported by the server.
+    pub total: usize,
+    /// Offset into the search collecting started at, from the request's
+    /// [page](struct.PostsRequestBuilder.html#method.pid).
+    pub offset: usize,
+    pub posts: Vec<Post>,
+    /// Amount of posts left out for a
+    /// [blacklisted](struct.PostsRequestBuilder.html#method.blacklist) tag.
+    pub blacklisted: usize,
+    /// Whether the request's [deadline](struct.PostsRequestBuilder.html#method.deadline) cut
+    /// collecting short.
+    pub deadline_exceeded: bool,
+    /// Whether collecting was [cancelled](struct.PostsRequestBuilder.html#method.cancel_on).
+    pub cancelled: bool,
+}
+
+impl CollectedPosts {
+    /// Whether every post matching the search from the starting offset on was collected,
+    /// counting blacklisted ones.
+    pub fn is_complete(&self) -> bool {
+        let seen = self.offset + self.posts.len() + self.blacklisted;
+        !self.deadline_exceeded && !self.cancelled && seen >= self.total
+    }
+}
+
+/// Size of a crawl through a search, as planned by
+/// [`PostsRequestBuilder::plan`](struct.PostsRequestBuilder.html#method.plan).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CrawlPlan {
+    /// Amount of posts matching the search.
+    pub total: usize,
+    /// Amount of pages [`fetch_all`](struct.PostsRequestBuilder.html#method.fetch_all) would
+    /// go through, within the pagination depth Gelbooru allows.
+    pub pages: usize,
+    /// Amount of requests those pages take.
+    pub estimated_requests: usize,
 }
 
 /// Post on Gelbooru
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[non_exhaustive]
 pub struct Post {
-    pub source: String,
+    /// Where the post was taken from, if known. May list several sources separated by spaces,
+    /// see [`sources`](#method.sources).
+    #[serde(default, deserialize_with = "crate::de::optional_string")]
+    pub source: Option<String>,
     pub directory: String,
+    #[serde(deserialize_with = "crate::de::number")]
     pub height: u64,
+    #[serde(deserialize_with = "crate::de::number")]
     pub id: u64,
     pub image: String,
+    #[serde(deserialize_with = "crate::de::number")]
     pub change: u64,
     pub owner: String,
+    /// Id of the parent post, if the post has one.
+    #[serde(default, deserialize_with = "crate::de::optional_id")]
     pub parent_id: Option<u64>,
     pub rating: String,
+    #[serde(deserialize_with = "crate::de::flag")]
     pub sample: u64,
+    #[serde(default, deserialize_with = "crate::de::number")]
     pub preview_height: u64,
+    #[serde(default, deserialize_with = "crate::de::number")]
     pub preview_width: u64,
+    #[serde(deserialize_with = "crate::de::number")]
     pub sample_height: u64,
+    #[serde(deserialize_with = "crate::de::number")]
     pub sample_width: u64,
-    pub score: u64,
-    pub tags: String,
-    pub title: String,
+    /// Negative for downvoted posts.
+    #[serde(deserialize_with = "crate::de::number_or_default")]
+    pub score: i64,
+    pub tags: PostTags,
+    #[serde(default, deserialize_with = "crate::de::optional_string")]
+    pub title: Option<String>,
+    #[serde(deserialize_with = "crate::de::number")]
     pub width: u64,
     pub file_url: String,
-    pub created_at: String,
+    #[serde(default)]
+    pub created_at: CreatedAt,
+    #[serde(default, deserialize_with = "crate::de::number")]
     pub post_locked: u64,
+    /// MD5 of the post's file. Left out by some Gelbooru-based sites, see
+    /// [`md5`](#method.md5) for one that's always there.
+    #[serde(default)]
+    pub md5: Option<String>,
+    #[serde(default)]
+    pub preview_url: Option<String>,
+    #[serde(default)]
+    pub sample_url: Option<String>,
+    /// Id of the uploader.
+    #[serde(default, deserialize_with = "crate::de::optional_number")]
+    pub creator_id: Option<u64>,
+    /// Moderation status, such as `active`, `flagged` or `pending`.
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default, deserialize_with = "crate::de::optional_flag")]
+    pub has_notes: Option<bool>,
+    #[serde(default, deserialize_with = "crate::de::optional_flag")]
+    pub has_comments: Option<bool>,
+    #[serde(default, deserialize_with = "crate::de::optional_flag")]
+    pub has_children: Option<bool>,
+    /// Fields not covered by the ones above, such as ones added to the API later.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+    // base URL of the site the post was requested from, unknown for deserialized posts
+    #[serde(skip)]
+    pub(crate) site: Option<Arc<str>>,
 }
 
-impl ApiQuery for PostQuery {}
+/// Creation date of a [`Post`](struct.Post.html), as sent by the API and, with the `chrono`
+/// feature, as parsed while deserializing.
+///
+/// Dates that fail to parse are kept as sent rather than failing the whole response.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CreatedAt {
+    raw: String,
+    #[cfg(feature = "chrono")]
+    parsed: Option<chrono::DateTime<chrono::FixedOffset>>,
+}
 
-impl Post {
-    pub fn id(&self) -> u64 {
-        self.id
+impl CreatedAt {
+    // e.g. `Sat Jan 02 03:04:05 -0500 2021`
+    #[cfg(feature = "chrono")]
+    const FORMAT: &'static str = "%a %b %d %H:%M:%S %z %Y";
+
+    fn new(raw: String) -> Self {
+        Self {
+            #[cfg(feature = "chrono")]
+            parsed: chrono::DateTime::parse_from_str(raw.trim(), Self::FORMAT).ok(),
+            raw,
+        }
     }
 
-    pub fn title<'a>(&'a self) -> &'a str {
-        &self.title
+    /// Date as sent by the API.
+    pub fn as_str(&self) -> &str {
+        &self.raw
     }
 
-    pub fn score(&self) -> u64 {
-        self.score
+    /// Parsed date, if it was in the expected format.
+    #[cfg(feature = "chrono")]
+    pub fn get(&self) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+        self.parsed
+    }
+}
+
+impl<'de> Deserialize<'de> for CreatedAt {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // null when missing, on some Gelbooru-based sites
+        let raw = Option::<String>::deserialize(deserializer)?;
+        Ok(Self::new(raw.unwrap_or_default()))
     }
+}
 
-    pub fn created_at(&self) -> chrono::DateTime<chrono::offset::FixedOffset> {
-        chrono::DateTime::parse_from_str(&self.created_at, "%a %b %d %H:%M:%S %z %Y")
-            .expect("failed to parse DateTime")
+// written back as sent, so serialized posts read the same as the API's
+impl Serialize for CreatedAt {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
     }
+}
 
-    pub fn rating<'a>(&'a self) -> Rating {
-        use crate::Rating::*;
-        match &self.rating[0..1] {
-            "s" => Safe,
-            "q" => Questionable,
-            "e" => Explicit,
-            _ => unreachable!("non-standard rating"),
+/// Tags of a [`Post`](struct.Post.html), split while deserializing, along with the
+/// space-separated string the API sent them as.
+///
+/// Dereferences to the list of tags.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PostTags {
+    raw: String,
+    list: Vec<String>,
+}
+
+impl PostTags {
+    fn new(raw: String) -> Self {
+        Self {
+            list: raw.split_whitespace().map(str::to_string).collect(),
+            raw,
         }
     }
 
-    pub fn owner<'a>(&'a self) -> &'a str {
-        &self.owner
+    /// Tags as sent by the API, separated by spaces.
+    pub fn as_str(&self) -> &str {
+        &self.raw
     }
+}
 
-    pub fn tags<'a>(&'a self) -> Vec<&'a str> {
-        self.tags.split(' ').collect()
+impl std::ops::Deref for PostTags {
+    type Target = [String];
+
+    fn deref(&self) -> &[String] {
+        &self.list
     }
+}
 
-    pub fn dimensions(&self) -> (u64, u64) {
-        (self.width, self.height)
+impl From<String> for PostTags {
+    fn from(raw: String) -> Self {
+        Self::new(raw)
     }
+}
 
-    pub fn image_url<'a>(&'a self) -> &'a str {
-        &self.file_url
+impl From<&str> for PostTags {
+    fn from(raw: &str) -> Self {
+        Self::new(raw.to_string())
     }
+}
 
-    pub fn source<'a>(&'a self) -> &'a str {
-        &self.source
+impl<'de> Deserialize<'de> for PostTags {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
     }
 }
 
-/// The content rating of a post.
-///
-/// See [this forum post](https://gelbooru.com/index.php?page=wiki&s=view&id=2535) for an in-depth explanation of the 3 ratings.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub enum Rating {
-    Safe,
-    Questionable,
-    Explicit,
+// written back as sent, like `CreatedAt`
+impl Serialize for PostTags {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
+    }
 }
 
-/// Request builder for the Posts endpoint.
-///
-/// See the [`posts`](fn.posts.html) function for proper usage.
-#[derive(Clone, Debug)]
-pub struct PostsRequestBuilder<'a> {
-    pub(crate) limit: Option<usize>,
-    pub(crate) tags: Vec<Cow<'a, str>>,
-    pub(crate) tags_raw: String,
-    pub(crate) rating: Option<Rating>,
-    pub(crate) sort_random: bool,
+impl ApiQuery for PostQuery {
+    #[cfg(feature = "xml")]
+    const ITEM: &'static str = "post";
+
+    fn from_json(body: &[u8]) -> Result<Self, Error> {
+        let first = body.iter().find(|byte| !byte.is_ascii_whitespace());
+        if first != Some(&b'[') {
+            return serde_json::from_slice(body).map_err(Error::JsonDeserialize);
+        }
+
+        let posts: Vec<Post> = serde_json::from_slice(body)?;
+        Ok(PostQuery {
+            attributes: Attributes {
+                limit: posts.len(),
+                offset: 0,
+                count: posts.len(),
+            },
+            posts,
+            request: None,
+            blacklisted: Vec::new(),
+            legacy: true,
+            raw: None,
+        })
+    }
+
+    fn keep_raw(&mut self, body: Bytes) {
+        self.raw = Some(body);
+    }
+
+    fn set_site(&mut self, base_url: &Arc<str>) {
+        for post in &mut self.posts {
+            post.site = Some(base_url.clone());
+        }
+    }
 }
 
-impl<'a> PostsRequestBuilder<'a> {
-    /// Amount of posts to recieve.
-    ///
-    /// When unspecified, default limit is 100, as set by the server.
-    pub fn limit(mut self, limit: usize) -> Self {
-        self.limit = Some(limit);
-        self
+// amount of posts matching a search on a Gelbooru 0.2 site, from its XML listing
+#[cfg(feature = "xml")]
+#[derive(Deserialize)]
+struct LegacyCount {
+    #[serde(rename = "@attributes")]
+    attributes: LegacyCountAttributes,
+}
+
+#[cfg(feature = "xml")]
+#[derive(Deserialize)]
+struct LegacyCountAttributes {
+    #[serde(deserialize_with = "crate::de::number")]
+    count: usize,
+}
+
+#[cfg(feature = "xml")]
+impl ApiQuery for LegacyCount {
+    const ITEM: &'static str = "post";
+    const XML_ONLY: bool = true;
+}
+
+impl PostQuery {
+    /// Whether no posts matched.
+    pub fn is_empty(&self) -> bool {
+        self.posts.is_empty()
     }
 
-    /// Add a single tag to the list of tags to search for.
-    /// To clear already set tags, see [`clear_tags`](#method.clear_tags).
-    ///
-    /// ## Example
-    /// ```rust
-    /// # use gelbooru_api::{Client, Error, posts};
-    /// # async fn example() -> Result<(), Error> {
-    /// # let client = Client::public();
-    /// posts()
-    ///     .tag("hello")
-    ///     .tag("world".to_string())
-    ///     .send(&client)
-    ///     .await?;
+    /// Body of the response this page was parsed from, if the client
+    /// [keeps them](../struct.Client.html#method.keep_raw_responses).
+    pub fn raw(&self) -> Option<&[u8]> {
+        self.raw.as_deref()
+    }
+
+    /// Posts dropped from the page for having a
+    /// [blacklisted](struct.PostsRequestBuilder.html#method.blacklist) tag.
+    pub fn blacklisted(&self) -> &[Post] {
+        &self.blacklisted
+    }
+
+    // amount of posts the server returned, including blacklisted ones
+    pub(crate) fn received(&self) -> usize {
+        self.posts.len() + self.blacklisted.len()
+    }
+
+    // whether no pages of the search are left after this one
+    pub(crate) fn is_exhausted(&self) -> bool {
+        self.received() == 0 || self.attributes.offset + self.received() >= self.attributes.count
+    }
+
+    /// Position after this page, to be saved and [resumed](struct.PostsRequestBuilder.html#method.resume)
+    /// later.
     ///
-    /// # Ok(())
-    /// # }
-    /// ```
-    pub fn tag<S: Into<Cow<'a, str>>>(mut self, tag: S) -> Self {
-        self.tags.push(tag.into());
-        self
+    /// Like [`next_page`](#method.next_page), this is `None` for pages that weren't returned by
+    /// a request.
+    pub fn cursor(&self) -> Option<PageCursor> {
+        let request = self.request.as_ref()?;
+        Some(PageCursor {
+            query: request.tags_query(),
+            limit: self.attributes.limit,
+            limit_policy: request.limit_policy.unwrap_or_default(),
+            offset: self.attributes.offset + self.received(),
+            blacklist: request
+                .blacklist
+                .iter()
+                .map(|tag| tag.to_string())
+                .collect(),
+            cid: request.cid,
+        })
     }
 
-    /// Tags to search for.
-    /// Any tag combination that works on the website will work here, including meta-tags.
+    /// Fetch the page following this one, or `None` if this was the last.
     ///
-    /// Can be chained; previously added tags are not overridden.
-    /// To clear already set tags, see [`clear_tags`](#method.clear_tags).
+    /// Only pages returned by [`PostsRequestBuilder::send`](struct.PostsRequestBuilder.html#method.send)
+    /// know how they were requested; for any other, such as deserialized ones, this is `None`.
     ///
     /// ## Example
     /// ```rust
     /// # use gelbooru_api::{Client, Error, posts};
     /// # async fn example() -> Result<(), Error> {
     /// # let client = Client::public();
-    /// posts()
-    ///     .tags(&["hello", "world"])
-    ///     .tags(&vec!["how", "are", "you?"])
-    ///     .send(&client)
-    ///     .await?;
-    ///
+    /// let mut page = posts().tags(&["step_arts"]).send(&client).await?;
+    /// loop {
+    ///     println!("{} posts at offset {}", page.posts.len(), page.attributes.offset);
+    ///     match page.next_page(&client).await? {
+    ///         Some(next) => page = next,
+    ///         None => break,
+    ///     }
+    /// }
     /// # Ok(())
     /// # }
     /// ```
-    pub fn tags<S: AsRef<str>>(mut self, tags: &'a [S]) -> Self {
-        let mut other = tags
-            .iter()
-            .map(|s| Cow::from(s.as_ref()))
-            .collect::<Vec<_>>();
-        self.tags.append(&mut other);
-        self
+    pub async fn next_page(&self, client: &Client) -> Result<Option<PostQuery>, Error> {
+        let request = match &self.request {
+            Some(request) => request,
+            None => return Ok(None),
+        };
+
+        let Attributes {
+            limit,
+            offset,
+            count,
+        } = self.attributes;
+        let next_offset = offset + limit;
+        if limit == 0 || next_offset >= count || next_offset >= MAX_PAGINATION_OFFSET {
+            return Ok(None);
+        }
+
+        let next = request.with_page(next_offset / limit);
+        next.send(client).await.map(Some)
     }
 
-    /// Append string directly to tag search
-    ///
-    /// !! These are not checked when being submitted !!
-    /// You can easily mess up the query construction using these.
-    ///
-    /// Probably only useful for setting meta-tags.
-    pub fn tags_raw<S: std::string::ToString>(mut self, raw_tags: S) -> Self {
-        self.tags_raw = raw_tags.to_string();
-        self
+    /// First post of the results.
+    pub fn first(&self) -> Option<&Post> {
+        self.posts.first()
     }
 
-    /// Clears the list of tags to search for.
-    /// Tags set using [`tags_raw`](#method.tags_raw) are also cleared.
+    /// The only post of the results, if any.
+    ///
+    /// Fails with [`Error::MultipleResults`](enum.Error.html#variant.MultipleResults) when there's
+    /// more than one, e.g. when looking up a post that should be unique.
     ///
     /// ## Example
     /// ```rust
     /// # use gelbooru_api::{Client, Error, posts};
     /// # async fn example() -> Result<(), Error> {
     /// # let client = Client::public();
-    /// posts()
-    ///     .tags(&["herro", "world"])
-    ///     .clear_tags() // wait, nevermind! clear tags.
-    ///     .tags(&["hello", "world"])
+    /// let post = posts()
+    ///     .tag("id:1234")
     ///     .send(&client)
-    ///     .await?;
+    ///     .await?
+    ///     .into_single()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn clear_tags(mut self) -> Self {
-        self.tags = Vec::new();
-        self.tags_raw = String::new();
-        self
+    pub fn into_single(self) -> Result<Option<Post>, Error> {
+        match self.posts.len() {
+            0 | 1 => Ok(self.posts.into_iter().next()),
+            count => Err(Error::MultipleResults { count }),
+        }
     }
 
-    /// Filter by content ratings.
-    /// See [`Rating`](enum.rating.html).
+    /// Randomly picked post of the results.
+    ///
+    /// Not cryptographically random; good for bots answering "random image" commands.
+    pub fn random_pick(&self) -> Option<&Post> {
+        if self.posts.is_empty() {
+            return None;
+        }
+
+        // std's hasher keys are randomly seeded, which is plenty for picking a post
+        let random = RandomState::new().build_hasher().finish();
+        self.posts.get((random % self.posts.len() as u64) as usize)
+    }
+
+    /// Group posts into families of a parent and its children.
+    ///
+    /// Only posts in this result set are considered; a post whose parent isn't present heads its
+    /// own family. Families are ordered by the first appearance of any of their members.
     ///
     /// ## Example
     /// ```rust
-    /// # use gelbooru_api::{Client, Error, Rating, posts};
+    /// # use gelbooru_api::{Client, Error, posts};
     /// # async fn example() -> Result<(), Error> {
     /// # let client = Client::public();
-    /// posts()
+    /// let query = posts()
     ///     .tags(&["hatsune_miku"])
-    ///     .rating(Rating::Safe)
     ///     .send(&client)
     ///     .await?;
     ///
+    /// for family in query.group_by_parent() {
+    ///     println!("{} with {} alternates", family.parent.id(), family.children.len());
+    /// }
     /// # Ok(())
     /// # }
     /// ```
-    pub fn rating(mut self, rating: Rating) -> Self {
-        self.rating = Some(rating);
-        self
+    pub fn group_by_parent(&self) -> Vec<PostFamily<'_>> {
+        let by_id: HashMap<u64, &Post> = self.posts.iter().map(|post| (post.id, post)).collect();
+
+        // walk up parents present in the result set; bounded in case of cyclic relations
+        let root_of = |mut id: u64| {
+            for _ in 0..self.posts.len() {
+                match by_id[&id].parent_id {
+                    Some(parent) if parent != id && by_id.contains_key(&parent) => id = parent,
+                    _ => break,
+                }
+            }
+            id
+        };
+
+        let mut families: Vec<PostFamily<'_>> = Vec::new();
+        let mut family_of: HashMap<u64, usize> = HashMap::new();
+        for post in &self.posts {
+            let root = root_of(post.id);
+            let index = *family_of.entry(root).or_insert_with(|| {
+                families.push(PostFamily {
+                    parent: by_id[&root],
+                    children: Vec::new(),
+                });
+                families.len() - 1
+            });
+            if root != post.id {
+                families[index].children.push(post);
+            }
+        }
+
+        families
     }
 
-    /// Randomize the order of posts.
-    ///
-    /// This is a server-side meta-tag feature, and is only provided for completeness' sake.
+    /// Sort posts by score, highest first.
+    pub fn sort_by_score(&mut self) {
+        self.posts.sort_by_key(|post| std::cmp::Reverse(post.score));
+    }
+
+    /// Sort posts by id, newest first.
+    pub fn sort_by_id(&mut self) {
+        self.posts.sort_by_key(|post| std::cmp::Reverse(post.id));
+    }
+
+    /// Sort posts by creation date, newest first.
+    #[cfg(feature = "chrono")]
+    pub fn sort_by_date(&mut self) {
+        self.posts
+            .sort_by_cached_key(|post| std::cmp::Reverse(post.created_at()));
+    }
+
+    /// Sort posts with a key extraction function.
+    /// The sort is stable, so posts with equal keys keep their order.
     ///
     /// ## Example
     /// ```rust
-    /// # use gelbooru_api::{Client, Error, Rating, posts};
+    /// # use gelbooru_api::{Client, Error, posts};
     /// # async fn example() -> Result<(), Error> {
     /// # let client = Client::public();
-    /// posts()
+    /// let mut query = posts()
     ///     .tags(&["hatsune_miku"])
-    ///     .random(true)
     ///     .send(&client)
     ///     .await?;
     ///
+    /// // largest images first
+    /// query.sort_by_key(|post| std::cmp::Reverse(post.width * post.height));
     /// # Ok(())
     /// # }
     /// ```
-    pub fn random(mut self, random: bool) -> Self {
-        self.sort_random = random;
-        self
+    pub fn sort_by_key<K: Ord, F: FnMut(&Post) -> K>(&mut self, key: F) {
+        self.posts.sort_by_key(key);
     }
+}
 
-    pub async fn send(self, client: &Client) -> Result<PostQuery, Error> {
-        let mut tags = String::new();
-        if let Some(rating) = self.rating {
-            tags.push_str(&format!("rating:{:?}+", rating).to_lowercase());
-        }
-        if self.sort_random {
-            tags.push_str("sort:random+");
-        }
-        tags.push_str(&self.tags.join("+"));
-        if !self.tags_raw.is_empty() {
-            tags.push('+');
-            tags.push_str(&self.tags_raw);
-        }
+impl Post {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
 
-        let mut qs: QueryStrings = Default::default();
-        qs.insert("s", "post".to_string());
-        qs.insert("limit", self.limit.unwrap_or(100).to_string());
-        qs.insert("tags", tags);
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
 
-        query_api(client, qs).await
+    pub fn score(&self) -> i64 {
+        self.score
     }
-}
 
-/// Tag on Gelbooru
-#[derive(Deserialize, Debug)]
-pub struct Tag {
-    pub id: u64,
-    pub name: String,
-    pub count: u64,
-    #[serde(rename = "type")]
-    pub tag_type: u64,
-    pub ambiguous: u64,
-}
+    /// Creation date, as sent by the API (e.g. `Sat Jan 02 03:04:05 -0500 2021`).
+    pub fn created_at_raw(&self) -> &str {
+        self.created_at.as_str()
+    }
 
-impl ApiQuery for TagQuery {}
+    /// Creation date, or `None` if the API sent it in an unexpected format.
+    #[cfg(feature = "chrono")]
+    pub fn created_at(&self) -> Option<chrono::DateTime<chrono::offset::FixedOffset>> {
+        self.try_created_at().ok()
+    }
 
-impl Tag {
-    pub fn id(&self) -> u64 {
-        self.id
+    /// Creation date, failing with [`Error::UnexpectedValue`](../enum.Error.html) if the API
+    /// sent it in an unexpected format.
+    #[cfg(feature = "chrono")]
+    pub fn try_created_at(&self) -> Result<chrono::DateTime<chrono::offset::FixedOffset>, Error> {
+        self.created_at.get().ok_or_else(|| Error::UnexpectedValue {
+            field: "creation date",
+            value: self.created_at.as_str().to_string(),
+        })
     }
 
-    #[deprecated(since="0.3.5", note="Use tag.name() instead")]
-    pub fn tag<'a>(&'a self) -> &'a str {
-        &self.name()
+    /// Creation date, for projects using `time` rather than `chrono`, or `None` if the API sent
+    /// it in an unexpected format.
+    #[cfg(feature = "time")]
+    pub fn created_at_time(&self) -> Option<time::OffsetDateTime> {
+        self.try_created_at_time().ok()
     }
 
-    pub fn name<'a>(&'a self) -> &'a str {
-        &self.name
+    /// Creation date, for projects using `time` rather than `chrono`, failing with
+    /// [`Error::UnexpectedValue`](../enum.Error.html) if the API sent it in an unexpected format.
+    #[cfg(feature = "time")]
+    pub fn try_created_at_time(&self) -> Result<time::OffsetDateTime, Error> {
+        let format = time::macros::format_description!(
+            "[weekday repr:short] [month repr:short] [day] [hour]:[minute]:[second] [offset_hour sign:mandatory][offset_minute] [year]"
+        );
+        time::OffsetDateTime::parse(self.created_at.as_str().trim(), format).map_err(|_| {
+            Error::UnexpectedValue {
+                field: "creation date",
+                value: self.created_at.as_str().to_string(),
+            }
+        })
     }
 
-    pub fn count(&self) -> u64 {
-        self.count
+    /// Time of the last change to this post, such as a tag edit, or `None` if the API sent a
+    /// timestamp out of range.
+    #[cfg(feature = "chrono")]
+    pub fn updated_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        chrono::DateTime::from_timestamp(i64::try_from(self.change).ok()?, 0)
     }
 
-    pub fn tag_type(&self) -> TagType {
-        use TagType::*;
-        match self.tag_type {
-            1 => Artist,
-            4 => Character,
-            3 => Copyright,
-            2 => Deprecated,
-            5 => Metadata,
-            0 => Tag,
-            _ => unreachable!("non-standard tag type"),
+    /// Time of the last change to this post, for projects using `time` rather than `chrono`, or
+    /// `None` if the API sent a timestamp out of range.
+    #[cfg(feature = "time")]
+    pub fn updated_at_time(&self) -> Option<time::OffsetDateTime> {
+        time::OffsetDateTime::from_unix_timestamp(i64::try_from(self.change).ok()?).ok()
+    }
+
+    /// Content rating of the post.
+    ///
+    /// Unknown ratings are treated as [`Explicit`](enum.Rating.html#variant.Explicit), the most
+    /// restrictive one, without telling them apart from actually explicit posts; use
+    /// [`try_rating`](#method.try_rating) to catch them.
+    pub fn rating(&self) -> Rating {
+        self.try_rating().unwrap_or(Rating::Explicit)
+    }
+
+    /// Content rating of the post, failing with
+    /// [`Error::UnexpectedValue`](../enum.Error.html) for unknown ratings.
+    pub fn try_rating(&self) -> Result<Rating, Error> {
+        use crate::Rating::*;
+        match self.rating.trim().to_lowercase().as_str() {
+            "general" | "g" => Ok(General),
+            "sensitive" => Ok(Sensitive),
+            "safe" | "s" => Ok(Safe),
+            "questionable" | "q" => Ok(Questionable),
+            "explicit" | "e" => Ok(Explicit),
+            _ => Err(Error::UnexpectedValue {
+                field: "rating",
+                value: self.rating.clone(),
+            }),
         }
     }
 
-    #[deprecated(since="0.3.5", note="Use tag.ambiguous() instead")]
-    pub fn ambigious(&self) -> bool {
-        self.ambiguous()
+    pub fn owner(&self) -> &str {
+        &self.owner
     }
 
-    pub fn ambiguous(&self) -> bool {
-        if self.ambiguous == 0 {
-            false
-        } else {
-            true
+    pub fn tags(&self) -> Vec<&str> {
+        self.tags.iter().map(String::as_str).collect()
+    }
+
+    /// Tags as sent by the API, separated by spaces.
+    pub fn tags_raw(&self) -> &str {
+        self.tags.as_str()
+    }
+
+    /// Whether the post is tagged with `tag`.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own| own == tag)
+    }
+
+    pub fn dimensions(&self) -> (u64, u64) {
+        (self.width, self.height)
+    }
+
+    /// Link to the post's page on the site it was requested from.
+    ///
+    /// Posts that weren't requested, such as deserialized ones, link to gelbooru.com.
+    pub fn post_url(&self) -> String {
+        format!(
+            "{}/index.php?page=post&s=view&id={}",
+            self.site
+                .as_deref()
+                .unwrap_or(crate::client::DEFAULT_BASE_URL),
+            self.id
+        )
+    }
+
+    pub fn image_url(&self) -> &str {
+        &self.file_url
+    }
+
+    /// Link to the post's thumbnail, as sent by the API or, on sites leaving it out, built from
+    /// the post's directory and image.
+    pub fn preview_url<'a>(&'a self) -> Cow<'a, str> {
+        match self.preview_url.as_deref() {
+            Some(url) if !url.is_empty() => Cow::Borrowed(url),
+            _ => Cow::Owned(format!(
+                "{}/thumbnails/{}/thumbnail_{}.jpg",
+                self.image_host(),
+                self.directory,
+                self.image_stem(),
+            )),
         }
     }
-}
 
-/// The type of a tag.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub enum TagType {
-    Artist,
-    Character,
-    Copyright,
-    Deprecated,
-    Metadata,
-    Tag,
+    /// Link to the post's downscaled sample, as sent by the API or, on sites leaving it out,
+    /// built from the post's directory and image.
+    ///
+    /// Posts small enough not to have a sample link to their full image instead.
+    pub fn sample_url<'a>(&'a self) -> Cow<'a, str> {
+        match self.sample_url.as_deref() {
+            Some(url) if !url.is_empty() => Cow::Borrowed(url),
+            _ if self.sample == 0 => Cow::Borrowed(self.image_url()),
+            _ => Cow::Owned(format!(
+                "{}/samples/{}/sample_{}.jpg",
+                self.image_host(),
+                self.directory,
+                self.image_stem(),
+            )),
+        }
+    }
+
+    // thumbnails and samples are served next to the full images, e.g. on `img3.gelbooru.com`;
+    // without a file URL to go by, Gelbooru-based sites other than Gelbooru itself serve them
+    // from the site
+    fn image_host(&self) -> &str {
+        match (self.file_url.find("/images/"), self.site.as_deref()) {
+            (Some(index), _) => &self.file_url[..index],
+            (None, Some(site)) if site != crate::client::DEFAULT_BASE_URL => site,
+            (None, _) => "https://img3.gelbooru.com",
+        }
+    }
+
+    // name of the image without its extension, which thumbnails and samples are named after
+    fn image_stem(&self) -> &str {
+        self.image
+            .rsplit_once('.')
+            .map_or(self.image.as_str(), |(stem, _)| stem)
+    }
+
+    /// MD5 of the post's file, falling back to the name of its image when not sent.
+    pub fn md5(&self) -> &str {
+        match &self.md5 {
+            Some(md5) if !md5.is_empty() => md5,
+            _ => self.image.split('.').next().unwrap_or_default(),
+        }
+    }
+
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    /// Every source of the post, as posts may list several separated by spaces.
+    pub fn sources(&self) -> Vec<&str> {
+        self.source()
+            .unwrap_or_default()
+            .split_whitespace()
+            .collect()
+    }
+
+    /// Tags of this post that are of the given type, according to a name-to-type map.
+    ///
+    /// Useful when tag types are already known, e.g. from a local tag database, and an
+    /// [`enriched`](struct.PostsRequestBuilder.html#method.enriched) query would be wasteful.
+    pub fn tags_of_type<'a>(
+        &'a self,
+        types: &HashMap<String, TagType>,
+        tag_type: TagType,
+    ) -> Vec<&'a str> {
+        self.tags()
+            .into_iter()
+            .filter(|name| types.get(*name) == Some(&tag_type))
+            .collect()
+    }
 }
 
-/// Determines what field sorts tags in a query.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub enum Ordering {
-    Date,
-    Count,
-    Name,
+/// A parent post and its children, as found in a single result set.
+///
+/// See [`PostQuery::group_by_parent`](struct.PostQuery.html#method.group_by_parent).
+#[derive(Clone, Debug)]
+pub struct PostFamily<'a> {
+    pub parent: &'a Post,
+    pub children: Vec<&'a Post>,
 }
 
-/// Request builder for the Tags endpoint.
+/// Post joined with the full records of its tags.
 ///
-/// See the [`tags`](fn.tags.html) function for proper usage.
+/// Tags the Tags endpoint couldn't resolve are left out.
 #[derive(Clone, Debug)]
-pub struct TagsRequestBuilder {
-    limit: Option<usize>,
-    after_id: Option<usize>,
-    order_by: Option<Ordering>,
-    ascending: Option<bool>,
+pub struct EnrichedPost {
+    pub post: Post,
+    pub tags: Vec<Tag>,
 }
 
-enum TagSearch<'a> {
-    Name(&'a str),
-    Names(Vec<&'a str>),
-    Pattern(&'a str),
+impl EnrichedPost {
+    /// Names of the tags that are of the given type.
+    pub fn tags_of_type(&self, tag_type: TagType) -> Vec<&str> {
+        self.tags
+            .iter()
+            .filter(|tag| tag.tag_type() == tag_type)
+            .map(|tag| tag.name())
+            .collect()
+    }
+
+    pub fn artists(&self) -> Vec<&str> {
+        self.tags_of_type(TagType::Artist)
+    }
+
+    pub fn characters(&self) -> Vec<&str> {
+        self.tags_of_type(TagType::Character)
+    }
+
+    pub fn copyrights(&self) -> Vec<&str> {
+        self.tags_of_type(TagType::Copyright)
+    }
 }
 
-impl TagsRequestBuilder {
-    pub(crate) fn new() -> Self {
-        Self {
-            limit: None,
-            after_id: None,
-            order_by: None,
-            ascending: None,
+/// The content rating of a post.
+///
+/// See [this forum post](https://gelbooru.com/index.php?page=wiki&s=view&id=2535) for an in-depth explanation of the ratings.
+///
+/// [`Post::rating`](struct.Post.html#method.rating) reads ratings it doesn't recognize as
+/// `Explicit`, while [`Post::try_rating`](struct.Post.html#method.try_rating) fails on them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rating {
+    /// Rating used before Gelbooru split it into general and sensitive.
+    Safe,
+    General,
+    Sensitive,
+    Questionable,
+    Explicit,
+}
+
+/// Field sorting posts in a search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Sort {
+    /// Upload order.
+    Id,
+    Score,
+    Rating,
+    /// Last time the post was changed.
+    Updated,
+    Width,
+    Height,
+    Random,
+}
+
+impl Rating {
+    pub(crate) fn metatag(self) -> &'static str {
+        use Rating::*;
+        match self {
+            Safe => "safe",
+            General => "general",
+            Sensitive => "sensitive",
+            Questionable => "questionable",
+            Explicit => "explicit",
         }
     }
 
-    /// Amount of tags to recieve.
-    ///
-    /// When unspecified, default limit is 100, as set by the server.
-    pub fn limit(mut self, limit: usize) -> Self {
-        self.limit = Some(limit);
-        self
+    pub(crate) fn from_metatag(metatag: &str) -> Option<Self> {
+        use Rating::*;
+        match metatag {
+            "safe" => Some(Safe),
+            "general" => Some(General),
+            "sensitive" => Some(Sensitive),
+            "questionable" => Some(Questionable),
+            "explicit" => Some(Explicit),
+            _ => None,
+        }
     }
+}
 
-    pub fn after_id(mut self, id: usize) -> Self {
-        self.after_id = Some(id);
-        self
+impl Sort {
+    pub(crate) fn metatag(self) -> &'static str {
+        use Sort::*;
+        match self {
+            Id => "id",
+            Score => "score",
+            Rating => "rating",
+            Updated => "updated",
+            Width => "width",
+            Height => "height",
+            Random => "random",
+        }
     }
 
-    pub fn ascending(mut self, ascending: bool) -> Self {
-        self.ascending = Some(ascending);
-        self
+    pub(crate) fn from_metatag(metatag: &str) -> Option<Self> {
+        use Sort::*;
+        match metatag {
+            "id" => Some(Id),
+            "score" => Some(Score),
+            "rating" => Some(Rating),
+            "updated" => Some(Updated),
+            "width" => Some(Width),
+            "height" => Some(Height),
+            "random" => Some(Random),
+            _ => None,
+        }
     }
+}
 
-    /// How tags are sorted.
-    /// _Date, Count, Name._
+/// Order in which concurrently fetched pages are delivered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FetchOrder {
+    /// In the order of the search, holding back pages that arrive early.
+    Ordered,
+    /// As soon as each page arrives.
+    Unordered,
+}
+
+/// What to do with a posts [`limit`](struct.PostsRequestBuilder.html#method.limit) above the
+/// 100 posts the API returns per request.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum LimitPolicy {
+    /// Request 100 posts, as the API would have truncated the limit to anyway.
+    #[default]
+    ClampSilently,
+    /// Fail with [`Error::LimitExceeded`](../enum.Error.html#variant.LimitExceeded) instead of
+    /// sending the request.
+    ErrorIfExceeded,
+    /// Request as many pages of 100 posts as it takes to fill the limit, returning them as one.
+    SplitIntoPages,
+}
+
+/// Request builder for the Posts endpoint.
+///
+/// See the [`posts`](fn.posts.html) function for proper usage.
+#[derive(Clone, Debug)]
+pub struct PostsRequestBuilder<'a> {
+    pub(crate) limit: Option<usize>,
+    pub(crate) tags: Vec<Cow<'a, str>>,
+    pub(crate) excluded_tags: Vec<Cow<'a, str>>,
+    pub(crate) blacklist: Vec<Cow<'a, str>>,
+    pub(crate) queries: Vec<Query>,
+    pub(crate) tags_raw: String,
+    pub(crate) rating: Option<Rating>,
+    // `Some(true)` for only animated posts, `Some(false)` for only stills
+    pub(crate) animated: Option<bool>,
+    pub(crate) sort: Option<Sort>,
+    pub(crate) ascending: Option<bool>,
+    pub(crate) pid: Option<usize>,
+    pub(crate) cid: Option<u64>,
+    pub(crate) limit_policy: Option<LimitPolicy>,
+    pub(crate) options: RequestOptions,
+}
+
+impl<'a> PostsRequestBuilder<'a> {
+    /// Rebuild the search of a post listing on the website, such as one copied from the browser.
     ///
-    /// This is mainly useful with [`send`](#method.send), but ordering works with the other search
-    /// methods as well ([`name`](#method.name), [`names`](#method.names), [`pattern`](#method.pattern))
+    /// The rating and sorting metatags are read into [`rating`](#method.rating) and
+    /// [`sort`](#method.sort), and the rest of the search is kept as a
+    /// [`Query`](../query/enum.Query.html). The page being viewed isn't carried over.
     ///
     /// ## Example
     /// ```rust
-    /// # use gelbooru_api::{Client, Error, Ordering, tags};
+    /// # use gelbooru_api::{Client, Error};
     /// # async fn example() -> Result<(), Error> {
     /// # let client = Client::public();
-    /// tags()
-    ///     .limit(5)                 // 5 tags
-    ///     .ascending(true)             // descending
-    ///     .order_by(Ordering::Date) // according to creation-time
+    /// use gelbooru_api::api::PostsRequestBuilder;
+    ///
+    /// PostsRequestBuilder::from_url(
+    ///     "https://gelbooru.com/index.php?page=post&s=list&tags=hatsune_miku+rating%3ageneral",
+    /// )?
+    /// .send(&client)
+    /// .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_url(url: &str) -> Result<Self, Error> {
+        let uri: hyper::Uri = url.parse()?;
+        let invalid = |reason| Error::InvalidSearchUrl {
+            url: url.to_string(),
+            reason,
+        };
+
+        let mut params = HashMap::new();
+        for pair in uri.query().unwrap_or_default().split('&') {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            params.insert(key, value);
+        }
+        if params.get("page") != Some(&"post") || params.get("s") != Some(&"list") {
+            return Err(invalid("not a post listing"));
+        }
+
+        let text = decode(params.get("tags").copied().unwrap_or_default())
+            .ok_or_else(|| invalid("tags aren't valid percent-encoded UTF-8"))?;
+        let parts = match text.parse()? {
+            Query::And(parts) => parts,
+            query => vec![query],
+        };
+
+        let mut builder = crate::posts();
+        for part in parts {
+            builder = match part {
+                Query::Meta(Meta::Rating(rating)) => builder.rating(rating),
+                Query::Meta(Meta::Sort(sort, ascending)) => builder.sort(sort).ascending(ascending),
+                query => builder.query(query),
+            };
+        }
+        Ok(builder)
+    }
+
+    /// Amount of posts to recieve.
+    ///
+    /// When unspecified, default limit is 100, as set by the server. The server doesn't return
+    /// more than 100 posts at once; see [`limit_policy`](#method.limit_policy) for larger limits.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// How to handle a [`limit`](#method.limit) above 100 posts. Clamps it by default.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, LimitPolicy, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// // sent as 5 requests of 100 posts
+    /// let query = posts()
+    ///     .tags(&["hatsune_miku"])
+    ///     .limit(500)
+    ///     .limit_policy(LimitPolicy::SplitIntoPages)
     ///     .send(&client)
     ///     .await?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn order_by(mut self, ordering: Ordering) -> Self {
-        self.order_by = Some(ordering);
+    pub fn limit_policy(mut self, policy: LimitPolicy) -> Self {
+        self.limit_policy = Some(policy);
         self
     }
 
-    /// Query for tags without name/pattern specifier.
+    /// Page of results to request, counting from 0.
+    /// Pages are [`limit`](#method.limit) posts long.
     ///
     /// ## Example
     /// ```rust
-    /// # use gelbooru_api::{Client, Error, Ordering, tags};
+    /// # use gelbooru_api::{Client, Error, posts};
     /// # async fn example() -> Result<(), Error> {
     /// # let client = Client::public();
-    /// tags()
-    ///     .limit(10)                 // 10 tags
-    ///     .ascending(false)             // descending
-    ///     .order_by(Ordering::Count) // according to count (how many posts have tag)
+    /// // posts 200 through 299
+    /// posts()
+    ///     .tags(&["hatsune_miku"])
+    ///     .limit(100)
+    ///     .pid(2)
     ///     .send(&client)
     ///     .await?;
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn send(self, client: &Client) -> Result<TagQuery, Error> {
-        self.search(client, None).await
+    pub fn pid(mut self, pid: usize) -> Self {
+        self.pid = Some(pid);
+        self
     }
 
-    /// Pull data for given tag
+    /// Add a single tag to the list of tags to search for.
+    /// To clear already set tags, see [`clear_tags`](#method.clear_tags).
     ///
     /// ## Example
     /// ```rust
-    /// # use gelbooru_api::{Client, Error, Ordering, tags};
+    /// # use gelbooru_api::{Client, Error, posts};
     /// # async fn example() -> Result<(), Error> {
     /// # let client = Client::public();
-    /// tags()
-    ///     .name(&client, "solo")
+    /// posts()
+    ///     .tag("hello")
+    ///     .tag("world".to_string())
+    ///     .send(&client)
     ///     .await?;
+    ///
     /// # Ok(())
     /// # }
-    pub async fn name<S: AsRef<str>>(self, client: &Client, name: S) -> Result<Option<Tag>, Error> {
-        let search = TagSearch::Name(name.as_ref());
-        self.search(client, Some(search))
-            .await
-            .map(|tags| tags.tags.into_iter().next())
+    /// ```
+    pub fn tag<S: Into<Cow<'a, str>>>(mut self, tag: S) -> Self {
+        self.tags.push(tag.into());
+        self
     }
 
-    /// Pull data for the specified tags
+    /// Tags to search for.
+    /// Any tag combination that works on the website will work here, including meta-tags.
     ///
-    /// Tag limit is automatically set to accompany all the names.
+    /// Can be chained; previously added tags are not overridden.
+    /// To clear already set tags, see [`clear_tags`](#method.clear_tags).
     ///
     /// ## Example
     /// ```rust
-    /// # use gelbooru_api::{Client, Error, Ordering, tags};
+    /// # use gelbooru_api::{Client, Error, posts};
     /// # async fn example() -> Result<(), Error> {
     /// # let client = Client::public();
-    /// tags()
-    ///     .names(&client, &["solo", "hatsune_miku"])
+    /// posts()
+    ///     .tags(&["hello", "world"])
+    ///     .tags(&vec!["how", "are", "you?"])
+    ///     .send(&client)
     ///     .await?;
+    ///
     /// # Ok(())
     /// # }
-    pub async fn names<S: AsRef<str>>(
-        self,
-        client: &Client,
-        names: &[S],
-    ) -> Result<TagQuery, Error> {
-        let names = names.iter().map(|name| name.as_ref()).collect();
-        let search = TagSearch::Names(names);
-        self.search(client, Some(search)).await
+    /// ```
+    pub fn tags<S: AsRef<str>>(mut self, tags: &'a [S]) -> Self {
+        let mut other = tags
+            .iter()
+            .map(|s| Cow::from(s.as_ref()))
+            .collect::<Vec<_>>();
+        self.tags.append(&mut other);
+        self
     }
 
-    /// Search for tags with a pattern.
+    /// Add a tag pattern, where `*` matches any amount of characters.
     ///
-    /// Use `_` for single-character wildcards and `%` for multi-character wildcards.
-    /// (`%choolgirl%` would act as `*choolgirl*` wildcard search)
+    /// Wildcards are passed through while anything else unsafe in the pattern is encoded, except
+    /// whitespace, which separates tags and makes sending fail with
+    /// [`Error::InvalidTag`](../enum.Error.html#variant.InvalidTag) like it does for plain tags.
     ///
     /// ## Example
     /// ```rust
-    /// # use gelbooru_api::{Client, Error, Ordering, tags};
+    /// # use gelbooru_api::{Client, Error, posts};
     /// # async fn example() -> Result<(), Error> {
     /// # let client = Client::public();
-    /// tags()
-    ///     .limit(10)
-    ///     .pattern(&client, "%o_o") // matches regex /.*o.o/
+    /// posts()
+    ///     .tag_wildcard("hatsune_*")
+    ///     .send(&client)
     ///     .await?;
     /// # Ok(())
     /// # }
-    pub async fn pattern<S: AsRef<str>>(
-        self,
-        client: &Client,
-        pattern: S,
-    ) -> Result<TagQuery, Error> {
-        let search = TagSearch::Pattern(pattern.as_ref());
-        self.search(client, Some(search)).await
-    }
-
+    /// ```
+    pub fn tag_wildcard<S: AsRef<str>>(self, pattern: S) -> Self {
+        let mut encoded = String::with_capacity(pattern.as_ref().len());
+        for c in pattern.as_ref().chars() {
+            match c {
+                '*' => encoded.push(c),
+                // left for `validate_tags` to reject
+                c if c.is_whitespace() => encoded.push(c),
+                c => encoded.push_str(&encode(c.encode_utf8(&mut [0; 4]))),
+            }
+        }
+        self.tag(encoded)
+    }
+
+    /// Add a tag matched fuzzily, so near misses such as typos still match.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// posts()
+    ///     .tag_fuzzy("hatsune_mkiu")
+    ///     .tag("solo")
+    ///     .send(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn tag_fuzzy<S: AsRef<str>>(self, tag: S) -> Self {
+        self.tag(format!("~{}", encode(tag.as_ref())))
+    }
+
+    /// Only posts with at least one of the given tags.
+    ///
+    /// Can be chained, every group having to match.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// // either of the twins, but solo
+    /// posts()
+    ///     .any_of(&["kagamine_rin", "kagamine_len"])
+    ///     .tag("solo")
+    ///     .send(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn any_of<S: AsRef<str>>(self, tags: &[S]) -> Self {
+        match tags {
+            [] => self,
+            tags => {
+                // encoded up front so the group passes validation as one tag, keeping its place
+                // among the other tags
+                let alternatives = tags.iter().map(|tag| Query::tag(tag.as_ref())).collect();
+                self.tag(Query::Or(alternatives).encode())
+            }
+        }
+    }
+
+    /// Search using a typed [`Query`](../query/enum.Query.html).
+    ///
+    /// Can be chained, and combined with tags set any other way.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// use gelbooru_api::query::{Comparison, Meta, Query};
+    ///
+    /// posts()
+    ///     .tag("hatsune_miku")
+    ///     .query(Query::Meta(Meta::Score(Comparison::AtLeast, 100)))
+    ///     .send(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn query(mut self, query: Query) -> Self {
+        self.queries.push(query);
+        self
+    }
+
+    /// Exclude posts with the given tag.
+    ///
+    /// Excluded tags are kept apart from those searched for. A leading `-` is dropped, as the tag
+    /// is negated anyway, and the rest is checked like searched tags when sending.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// posts()
+    ///     .tags(&["hatsune_miku"])
+    ///     .exclude_tag("comic")
+    ///     .send(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn exclude_tag<S: Into<Cow<'a, str>>>(mut self, tag: S) -> Self {
+        self.excluded_tags.push(strip_negation(tag.into()));
+        self
+    }
+
+    /// Exclude posts with any of the given tags.
+    ///
+    /// Can be chained; previously excluded tags are not overridden.
+    pub fn exclude_tags<S: AsRef<str>>(mut self, tags: &'a [S]) -> Self {
+        self.excluded_tags
+            .extend(tags.iter().map(|s| strip_negation(Cow::from(s.as_ref()))));
+        self
+    }
+
+    /// Drop posts with any of the given tags once they're received, rather than excluding them
+    /// in the search.
+    ///
+    /// Blacklisted tags don't count against the search's tag limit, but pages come back short
+    /// by the posts dropped (see [`PostQuery::blacklisted`](struct.PostQuery.html#method.blacklisted)).
+    /// Can be chained; previously blacklisted tags are not overridden.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// posts()
+    ///     .tags(&["hatsune_miku"])
+    ///     .blacklist(&["comic", "monochrome"])
+    ///     .send(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn blacklist<S: AsRef<str>>(mut self, tags: &'a [S]) -> Self {
+        self.blacklist
+            .extend(tags.iter().map(|s| Cow::from(s.as_ref())));
+        self
+    }
+
+    /// Add a tag that's sent as is, skipping the checks [`send`](#method.send) makes.
+    ///
+    /// For intentionally raw input, such as pre-encoded metatags; like
+    /// [`tags_raw`](#method.tags_raw), it's easy to break the query with these.
+    pub fn tag_unchecked<S: AsRef<str>>(mut self, tag: S) -> Self {
+        if !self.tags_raw.is_empty() {
+            self.tags_raw.push('+');
+        }
+        self.tags_raw.push_str(tag.as_ref());
+        self
+    }
+
+    /// Append string directly to tag search
+    ///
+    /// !! These are not checked when being submitted !!
+    /// You can easily mess up the query construction using these.
+    ///
+    /// Probably only useful for setting meta-tags.
+    pub fn tags_raw<S: std::string::ToString>(mut self, raw_tags: S) -> Self {
+        self.tags_raw = raw_tags.to_string();
+        self
+    }
+
+    /// Clears the list of tags to search for.
+    /// Tags set using [`tags_raw`](#method.tags_raw) are also cleared.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// posts()
+    ///     .tags(&["herro", "world"])
+    ///     .clear_tags() // wait, nevermind! clear tags.
+    ///     .tags(&["hello", "world"])
+    ///     .send(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn clear_tags(mut self) -> Self {
+        self.tags = Vec::new();
+        self.excluded_tags = Vec::new();
+        self.queries = Vec::new();
+        self.tags_raw = String::new();
+        self
+    }
+
+    /// Filter by content ratings.
+    /// See [`Rating`](enum.rating.html).
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, Rating, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// posts()
+    ///     .tags(&["hatsune_miku"])
+    ///     .rating(Rating::Safe)
+    ///     .send(&client)
+    ///     .await?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn rating(mut self, rating: Rating) -> Self {
+        self.rating = Some(rating);
+        self
+    }
+
+    /// Exclude posts with the given content rating.
+    ///
+    /// Can be chained to exclude several ratings. Kept with the
+    /// [excluded tags](#method.exclude_tag), so [`clear_tags`](#method.clear_tags) clears it too.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, Rating, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// // everything but explicit posts
+    /// posts()
+    ///     .tags(&["hatsune_miku"])
+    ///     .exclude_rating(Rating::Explicit)
+    ///     .send(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn exclude_rating(self, rating: Rating) -> Self {
+        self.exclude_tag(format!("rating:{}", rating.metatag()))
+    }
+
+    /// Only posts that are animated, whether GIFs or videos.
+    ///
+    /// Replaces [`exclude_animated`](#method.exclude_animated); passing `false` clears either.
+    pub fn only_animated(mut self, only_animated: bool) -> Self {
+        self.animated = if only_animated { Some(true) } else { None };
+        self
+    }
+
+    /// Exclude posts that are animated, whether GIFs or videos, leaving only stills.
+    ///
+    /// Replaces [`only_animated`](#method.only_animated); passing `false` clears either.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// posts()
+    ///     .tags(&["scenery"])
+    ///     .exclude_animated(true)
+    ///     .send(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn exclude_animated(mut self, exclude_animated: bool) -> Self {
+        self.animated = if exclude_animated { Some(false) } else { None };
+        self
+    }
+
+    /// Randomize the order of posts.
+    ///
+    /// Shorthand for [`sort`](#method.sort) with [`Sort::Random`](enum.Sort.html#variant.Random),
+    /// where `false` undoes it.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, Rating, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// posts()
+    ///     .tags(&["hatsune_miku"])
+    ///     .random(true)
+    ///     .send(&client)
+    ///     .await?;
+    ///
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn random(mut self, random: bool) -> Self {
+        if random {
+            self.sort = Some(Sort::Random);
+        } else if self.sort == Some(Sort::Random) {
+            self.sort = None;
+        }
+        self
+    }
+
+    /// How posts are sorted.
+    ///
+    /// Descending unless set otherwise with [`ascending`](#method.ascending).
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, Sort, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// // lowest scoring first
+    /// posts()
+    ///     .tags(&["hatsune_miku"])
+    ///     .sort(Sort::Score)
+    ///     .ascending(true)
+    ///     .send(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sort(mut self, sort: Sort) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    /// Direction of the [`sort`](#method.sort). Has no effect on random order.
+    pub fn ascending(mut self, ascending: bool) -> Self {
+        self.ascending = Some(ascending);
+        self
+    }
+
+    /// Only posts changed at the given Unix timestamp, as in
+    /// [`Post::change`](struct.Post.html#structfield.change).
+    ///
+    /// Sent as the API's `cid` parameter, for tracking edits to posts rather than uploads.
+    pub fn changed_at(mut self, timestamp: u64) -> Self {
+        self.cid = Some(timestamp);
+        self
+    }
+
+    /// Only posts with an id lower than the given one, i.e. uploaded before it.
+    pub fn id_before(self, id: u64) -> Self {
+        self.tag(format!("id:{}{}", encode("<"), id))
+    }
+
+    /// Only posts with an id higher than the given one, i.e. uploaded after it.
+    pub fn id_after(self, id: u64) -> Self {
+        self.tag(format!("id:{}{}", encode(">"), id))
+    }
+
+    /// Only posts newer than the one with the given id, for fetching what's new since a
+    /// previous run. Same as [`id_after`](#method.id_after).
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// let newest_seen = 8_000_000;
+    /// let new = posts()
+    ///     .tags(&["hatsune_miku"])
+    ///     .since_id(newest_seen)
+    ///     .send(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn since_id(self, id: u64) -> Self {
+        self.id_after(id)
+    }
+
+    /// Only posts with an id from `first` through `last`, inclusive.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// posts()
+    ///     .id_between(1000, 1099)
+    ///     .send(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn id_between(self, first: u64, last: u64) -> Self {
+        self.tag(format!("id:{}{}", encode(">="), first))
+            .tag(format!("id:{}{}", encode("<="), last))
+    }
+
+    /// Only posts uploaded after the given day.
+    #[cfg(feature = "chrono")]
+    pub fn posted_after(self, date: chrono::NaiveDate) -> Self {
+        self.tag(format!("date:{}{}", encode(">"), date.format("%Y-%m-%d")))
+    }
+
+    /// Only posts uploaded before the given day.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// use chrono::NaiveDate;
+    ///
+    /// // posts from 2020
+    /// posts()
+    ///     .posted_after(NaiveDate::from_ymd_opt(2019, 12, 31).unwrap())
+    ///     .posted_before(NaiveDate::from_ymd_opt(2021, 1, 1).unwrap())
+    ///     .send(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "chrono")]
+    pub fn posted_before(self, date: chrono::NaiveDate) -> Self {
+        self.tag(format!("date:{}{}", encode("<"), date.format("%Y-%m-%d")))
+    }
+
+    /// Only posts at least the given amount of pixels wide.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// // wallpapers
+    /// posts()
+    ///     .tags(&["scenery"])
+    ///     .min_width(1920)
+    ///     .min_height(1080)
+    ///     .send(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn min_width(self, width: u32) -> Self {
+        self.tag(format!("width:{}{}", encode(">="), width))
+    }
+
+    /// Only posts at least the given amount of pixels high.
+    pub fn min_height(self, height: u32) -> Self {
+        self.tag(format!("height:{}{}", encode(">="), height))
+    }
+
+    /// Only posts exactly the given amount of pixels wide.
+    pub fn width(self, width: u32) -> Self {
+        self.tag(format!("width:{}", width))
+    }
+
+    /// Only posts exactly the given amount of pixels high.
+    pub fn height(self, height: u32) -> Self {
+        self.tag(format!("height:{}", height))
+    }
+
+    /// Only posts uploaded by the given user.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// posts()
+    ///     .uploader("danbooru")
+    ///     .send(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn uploader<S: AsRef<str>>(self, name: S) -> Self {
+        self.tag(format!(
+            "user:{}",
+            encode(&name.as_ref().trim().replace(' ', "_"))
+        ))
+    }
+
+    /// Only posts not uploaded by the given user.
+    pub fn not_uploader<S: AsRef<str>>(self, name: S) -> Self {
+        self.tag(format!(
+            "-user:{}",
+            encode(&name.as_ref().trim().replace(' ', "_"))
+        ))
+    }
+
+    /// Only the post whose file has the given MD5 hash.
+    ///
+    /// Same as [`md5_tag`](#method.md5_tag). See [`find_by_md5`](../fn.find_by_md5.html) for
+    /// looking up a single post.
+    pub fn md5<S: AsRef<str>>(self, md5: S) -> Self {
+        self.md5_tag(md5)
+    }
+
+    /// Only the post whose file has the given MD5 hash, combined with the other filters.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, Rating, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// let query = posts()
+    ///     .rating(Rating::Safe)
+    ///     .md5_tag("d41d8cd98f00b204e9800998ecf8427e")
+    ///     .send(&client)
+    ///     .await?;
+    ///
+    /// if query.is_empty() {
+    ///     println!("not a safe-rated post");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn md5_tag<S: AsRef<str>>(self, md5: S) -> Self {
+        let md5 = md5.as_ref().trim().to_lowercase();
+        self.tag(format!("md5:{}", encode(&md5)))
+    }
+
+    /// Owned copy of this builder, to keep around as the template of a saved search.
+    ///
+    /// Unlike the builder, the template doesn't borrow the tags it was given, so it can be
+    /// stored for as long as needed. Requests are made from it with the `with_*` methods,
+    /// which leave the template untouched.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # use gelbooru_api::api::PostsRequestBuilder;
+    /// struct SavedSearch {
+    ///     name: String,
+    ///     template: PostsRequestBuilder<'static>,
+    /// }
+    ///
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// let tags = vec!["hatsune_miku".to_string(), "solo".to_string()];
+    /// let saved = SavedSearch {
+    ///     name: "miku".to_string(),
+    ///     template: posts().tags(&tags).limit(20).template(),
+    /// };
+    /// drop(tags);
+    ///
+    /// let page = saved.template.with_page(2).send(&client).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn template(&self) -> PostsRequestBuilder<'static> {
+        self.clone().into_owned()
+    }
+
+    /// Copy of this builder requesting the given page.
+    ///
+    /// The `with_*` methods leave the original untouched, so a builder can serve as a
+    /// [`template`](#method.template) for saved searches.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, Rating, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// let template = posts().tags(&["hatsune_miku"]).limit(20).template();
+    ///
+    /// let first = template.with_page(0).send(&client).await?;
+    /// let second = template.with_page(1).send(&client).await?;
+    /// let explicit = template.with_rating(Rating::Explicit).send(&client).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_page(&self, pid: usize) -> Self {
+        let mut builder = self.clone();
+        builder.pid = Some(pid);
+        builder
+    }
+
+    /// Copy of this builder filtering by the given rating.
+    /// See [`with_page`](#method.with_page).
+    pub fn with_rating(&self, rating: Rating) -> Self {
+        self.clone().rating(rating)
+    }
+
+    /// Copy of this builder with the given limit.
+    /// See [`with_page`](#method.with_page).
+    pub fn with_limit(&self, limit: usize) -> Self {
+        self.clone().limit(limit)
+    }
+
+    /// Copy of this builder with additional tags.
+    /// See [`with_page`](#method.with_page).
+    pub fn with_tags<S: AsRef<str>>(&self, tags: &'a [S]) -> Self {
+        self.clone().tags(tags)
+    }
+
+    /// Combine two builders.
+    ///
+    /// Tags of both are searched for; for any other setting, `other` takes precedence where
+    /// it's set.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, Rating, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// let safe = posts().rating(Rating::Safe).limit(10);
+    /// let miku = posts().tags(&["hatsune_miku"]);
+    ///
+    /// safe.merge(miku).send(&client).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn merge(mut self, other: Self) -> Self {
+        self.limit = other.limit.or(self.limit);
+        self.tags.extend(other.tags);
+        self.excluded_tags.extend(other.excluded_tags);
+        self.blacklist.extend(other.blacklist);
+        self.queries.extend(other.queries);
+        if self.tags_raw.is_empty() {
+            self.tags_raw = other.tags_raw;
+        } else if !other.tags_raw.is_empty() {
+            self.tags_raw = format!("{}+{}", self.tags_raw, other.tags_raw);
+        }
+        self.rating = other.rating.or(self.rating);
+        self.animated = other.animated.or(self.animated);
+        self.sort = other.sort.or(self.sort);
+        self.ascending = other.ascending.or(self.ascending);
+        self.pid = other.pid.or(self.pid);
+        self.cid = other.cid.or(self.cid);
+        self.limit_policy = other.limit_policy.or(self.limit_policy);
+        self.options.auth = other.options.auth.or(self.options.auth);
+        self.options.deadline = other.options.deadline.or(self.options.deadline);
+        self.options.timeout = other.options.timeout.or(self.options.timeout);
+        self.options.cancel = other.options.cancel.or(self.options.cancel);
+        self
+    }
+
+    /// Continue a search from a saved [`PageCursor`](struct.PageCursor.html).
+    ///
+    /// The cursor's search replaces this builder's tags, rating, blacklist, limit and page,
+    /// while credentials and deadline are kept.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// let page = posts().tags(&["step_arts"]).send(&client).await?;
+    /// let saved = serde_json::to_string(&page.cursor().unwrap()).unwrap();
+    ///
+    /// // after a restart
+    /// let cursor = serde_json::from_str(&saved).unwrap();
+    /// let next = posts().resume(cursor).send(&client).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resume(mut self, cursor: PageCursor) -> Self {
+        self.tags.clear();
+        self.excluded_tags.clear();
+        self.queries.clear();
+        self.tags_raw = cursor.query;
+        self.rating = None;
+        self.animated = None;
+        self.sort = None;
+        self.ascending = None;
+        self.blacklist = cursor.blacklist.into_iter().map(Cow::Owned).collect();
+        self.cid = cursor.cid;
+        self.limit = Some(cursor.limit);
+        self.limit_policy = Some(cursor.limit_policy);
+        // pages as they'll be requested, as the policy may clamp the limit
+        self.pid = Some(match self.page_size() {
+            0 => 0,
+            page_size => cursor.offset / page_size,
+        });
+        self
+    }
+
+    request_options!();
+
+    /// Send the request.
+    ///
+    /// The builder is only borrowed, so it can be reused for further requests; the returned page
+    /// keeps its own copy for [`next_page`](struct.PostQuery.html#method.next_page) and
+    /// [`cursor`](struct.PostQuery.html#method.cursor).
+    pub async fn send(&self, client: &Client) -> Result<PostQuery, Error> {
+        self.validate_tags()?;
+
+        self.check_limit()?;
+
+        let mut query: PostQuery = match self.limit_policy.unwrap_or_default() {
+            LimitPolicy::SplitIntoPages if self.page_size() > MAX_LIMIT => {
+                self.send_split(client).await?
+            }
+            _ => query_api(client, self.query_strings(), &self.options).await?,
+        };
+        if query.legacy {
+            // the count is unknown, so it's kept one ahead while pages come back full
+            let limit = self.page_size();
+            let received = query.posts.len();
+            let offset = self.pid.unwrap_or(0) * limit;
+            query.attributes = Attributes {
+                limit,
+                offset,
+                count: offset + received + (received >= limit.max(1)) as usize,
+            };
+        }
+        if !self.blacklist.is_empty() {
+            let (blacklisted, posts) = query
+                .posts
+                .into_iter()
+                .partition(|post| self.is_blacklisted(post));
+            query.posts = posts;
+            query.blacklisted = blacklisted;
+        }
+        query.request = Some(self.clone().into_owned());
+        Ok(query)
+    }
+
+    /// URL the request would be sent to, without sending it.
+    ///
+    /// Credentials set with [`auth`](#method.auth) are included, but those of the client's
+    /// [`AuthPool`](../struct.AuthPool.html) aren't, as which key is used is only decided when
+    /// sending.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// let url = posts().tags(&["hatsune_miku"]).limit(10).build_url(&client)?;
+    /// println!("would request {}", url);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn build_url(&self, client: &Client) -> Result<hyper::Uri, Error> {
+        self.validate_tags()?;
+        self.check_limit()?;
+        let (uri, _) = request_uri::<PostQuery>(
+            client,
+            self.query_strings(),
+            self.options.auth.clone().flatten(),
+        )?;
+        Ok(uri)
+    }
+
+    // the pages covering the requested one, trimmed to it; pages are only ever requested at
+    // the API's limit, so they line up with its page numbers
+    async fn send_split(&self, client: &Client) -> Result<PostQuery, Error> {
+        let limit = self.page_size();
+        let offset = self.pid.unwrap_or(0) * limit;
+        let first_pid = offset / MAX_LIMIT;
+        let last_pid = (offset + limit - 1) / MAX_LIMIT;
+
+        let mut query: Option<PostQuery> = None;
+        for pid in first_pid..=last_pid {
+            let mut page = self.clone();
+            page.limit = Some(MAX_LIMIT);
+            page.pid = Some(pid);
+            let mut received: PostQuery =
+                query_api(client, page.query_strings(), &self.options).await?;
+
+            let exhausted = received.posts.len() < MAX_LIMIT;
+            match &mut query {
+                Some(query) => query.posts.append(&mut received.posts),
+                None => query = Some(received),
+            }
+            if exhausted {
+                break;
+            }
+        }
+
+        let mut query = query.expect("at least one page is requested");
+        let skipped = (offset - first_pid * MAX_LIMIT).min(query.posts.len());
+        query.posts.drain(..skipped);
+        query.posts.truncate(limit);
+        query.attributes.limit = limit;
+        query.attributes.offset = offset;
+        Ok(query)
+    }
+
+    fn check_limit(&self) -> Result<(), Error> {
+        match (self.limit_policy.unwrap_or_default(), self.limit) {
+            (LimitPolicy::ErrorIfExceeded, Some(limit)) if limit > MAX_LIMIT => {
+                Err(Error::LimitExceeded {
+                    limit,
+                    max: MAX_LIMIT,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // amount of posts a page holds, accounting for the limit policy
+    pub(crate) fn page_size(&self) -> usize {
+        let limit = self.limit.unwrap_or(MAX_LIMIT);
+        match self.limit_policy.unwrap_or_default() {
+            LimitPolicy::SplitIntoPages => limit,
+            _ => limit.min(MAX_LIMIT),
+        }
+    }
+
+    fn query_strings(&self) -> QueryStrings<'static> {
+        let mut qs: QueryStrings = Default::default();
+        qs.insert("s", "post".to_string());
+        qs.insert("limit", self.page_size().min(MAX_LIMIT).to_string());
+        qs.insert("tags", self.tags_query());
+        if let Some(pid) = self.pid {
+            qs.insert("pid", pid.to_string());
+        }
+        if let Some(cid) = self.cid {
+            qs.insert("cid", cid.to_string());
+        }
+        qs
+    }
+
+    /// Plan a crawl of every post matching the request, from a single request counting them.
+    ///
+    /// Pages start at the builder's [`pid`](#method.pid) and are [`limit`](#method.limit)
+    /// posts long, as with [`fetch_all`](#method.fetch_all).
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// let search = posts().tags(&["hatsune_miku"]);
+    /// let plan = search.clone().plan(&client).await?;
+    /// if plan.estimated_requests <= 50 {
+    ///     search.fetch_all(&client, plan.total).await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn plan(self, client: &Client) -> Result<CrawlPlan, Error> {
+        let page_size = self.page_size();
+        let offset = self.pid.unwrap_or(0) * page_size;
+
+        let total = self.count(client).await?;
+        let pages = match page_size {
+            0 => 0,
+            page_size => total
+                .min(MAX_PAGINATION_OFFSET)
+                .saturating_sub(offset)
+                .div_ceil(page_size),
+        };
+        Ok(CrawlPlan {
+            total,
+            pages,
+            estimated_requests: pages * page_size.div_ceil(MAX_LIMIT),
+        })
+    }
+
+    /// Amount of posts matching the request, without fetching any of them.
+    ///
+    /// [Legacy](../struct.ClientBuilder.html#method.legacy_api) sites only report it in XML
+    /// responses, so counting on them needs the `xml` feature, failing with
+    /// [`Error::CountUnavailable`](../enum.Error.html#variant.CountUnavailable) without it.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// let count = posts().tags(&["hatsune_miku"]).count(&client).await?;
+    /// println!("{} posts of Miku", count);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn count(mut self, client: &Client) -> Result<usize, Error> {
+        self.limit = Some(0);
+        self.pid = None;
+        if client.legacy_api {
+            return self.legacy_count(client).await;
+        }
+        let query = self.send(client).await?;
+        Ok(query.attributes.count)
+    }
+
+    // Gelbooru 0.2 sites leave the count out of JSON responses, but not out of XML ones
+    #[cfg(feature = "xml")]
+    async fn legacy_count(&self, client: &Client) -> Result<usize, Error> {
+        self.validate_tags()?;
+        let count: LegacyCount = query_api(client, self.query_strings(), &self.options).await?;
+        Ok(count.attributes.count)
+    }
+
+    #[cfg(not(feature = "xml"))]
+    async fn legacy_count(&self, _client: &Client) -> Result<usize, Error> {
+        Err(Error::CountUnavailable)
+    }
+
+    // tags that would break the query string, or silently turn into several tags
+    pub(crate) fn validate_tags(&self) -> Result<(), Error> {
+        for tag in self.tags.iter().chain(&self.excluded_tags) {
+            let reason = if tag.chars().any(char::is_whitespace) {
+                "contains whitespace, which separates tags"
+            } else if tag.contains('+') {
+                "contains `+`, which separates tags"
+            } else if tag.contains('&') {
+                "contains `&`, which ends the tags query string"
+            } else if tag.contains('#') {
+                "contains `#`, which ends the URL"
+            } else {
+                continue;
+            };
+
+            return Err(Error::InvalidTag {
+                tag: tag.to_string(),
+                reason,
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn is_blacklisted(&self, post: &Post) -> bool {
+        self.blacklist
+            .iter()
+            .any(|blacklisted| post.has_tag(blacklisted))
+    }
+
+    // value of the `tags` query string
+    pub(crate) fn tags_query(&self) -> String {
+        self.as_query().encode()
+    }
+
+    // everything searched for, as one query; tags set as strings are checked by `validate_tags`
+    // instead, so they're passed on as is
+    pub(crate) fn as_query(&self) -> Query {
+        let mut parts = Vec::new();
+        if let Some(rating) = self.rating {
+            parts.push(Query::Meta(Meta::Rating(rating)));
+        }
+        if let Some(sort) = self.sort {
+            parts.push(Query::Meta(Meta::Sort(sort, self.ascending == Some(true))));
+        }
+        // not every video is tagged `animated`, nor every WebM `video`
+        let animated = ANIMATED_TAGS.iter().map(|&tag| Query::tag(tag));
+        match self.animated {
+            Some(true) => parts.push(Query::Or(animated.collect())),
+            Some(false) => parts.extend(animated.map(|tag| Query::Not(Box::new(tag)))),
+            None => {}
+        }
+        parts.extend(self.tags.iter().map(|tag| Query::Raw(tag.to_string())));
+        parts.extend(
+            self.excluded_tags
+                .iter()
+                .map(|tag| Query::Not(Box::new(Query::Raw(tag.to_string())))),
+        );
+        parts.extend(self.queries.iter().cloned());
+        parts.push(Query::Raw(self.tags_raw.clone()));
+        Query::And(parts)
+    }
+
+    // copy that owns its tags, so it can outlive borrowed ones
+    pub(crate) fn into_owned(self) -> PostsRequestBuilder<'static> {
+        PostsRequestBuilder {
+            limit: self.limit,
+            tags: self
+                .tags
+                .into_iter()
+                .map(|tag| Cow::Owned(tag.into_owned()))
+                .collect(),
+            excluded_tags: self
+                .excluded_tags
+                .into_iter()
+                .map(|tag| Cow::Owned(tag.into_owned()))
+                .collect(),
+            blacklist: self
+                .blacklist
+                .into_iter()
+                .map(|tag| Cow::Owned(tag.into_owned()))
+                .collect(),
+            queries: self.queries,
+            tags_raw: self.tags_raw,
+            rating: self.rating,
+            animated: self.animated,
+            sort: self.sort,
+            ascending: self.ascending,
+            pid: self.pid,
+            cid: self.cid,
+            limit_policy: self.limit_policy,
+            options: self.options,
+        }
+    }
+
+    /// Fetch every post matching the request, requesting up to `concurrency` pages at once.
+    ///
+    /// The first page is requested on its own to learn the total amount of posts, after which
+    /// the remaining pages are fetched concurrently, delivered according to `order`.
+    /// Gelbooru refuses to page further than 20,000 posts into a search, so larger result sets
+    /// come back incomplete (see [`CollectedPosts::is_complete`](struct.CollectedPosts.html#method.is_complete)).
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, FetchOrder, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// let collected = posts()
+    ///     .tags(&["step_arts"])
+    ///     .fetch_concurrent(&client, 4, FetchOrder::Ordered)
+    ///     .await?;
+    ///
+    /// println!("{} of {} posts", collected.posts.len(), collected.total);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn fetch_concurrent(
+        self,
+        client: &Client,
+        concurrency: usize,
+        order: FetchOrder,
+    ) -> Result<CollectedPosts, Error> {
+        let mut collected = CollectedPosts {
+            total: 0,
+            offset: self.pid.unwrap_or(0) * self.page_size(),
+            posts: Vec::new(),
+            blacklisted: 0,
+            deadline_exceeded: false,
+            cancelled: false,
+        };
+
+        let mut total = None;
+        let mut pages = self.fetch_pages(client, concurrency, order);
+        // pages still in flight are dropped along with the stream when stopping early
+        while let Some(page) = pages.next().await {
+            match page {
+                Ok(page) => {
+                    // without a count, legacy pages only settle on the total at the last one
+                    if page.legacy {
+                        total = Some(page.attributes.count);
+                    } else {
+                        total.get_or_insert(page.attributes.count);
+                    }
+                    collected.blacklisted += page.blacklisted.len();
+                    collected.posts.extend(page.posts);
+                }
+                Err(Error::DeadlineExceeded) => {
+                    collected.deadline_exceeded = true;
+                    break;
+                }
+                Err(Error::Cancelled) => {
+                    collected.cancelled = true;
+                    break;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        collected.total = total.unwrap_or(0);
+        Ok(collected)
+    }
+
+    /// Fetch every page matching the request, requesting up to `concurrency` pages at once and
+    /// yielding each page as it's delivered according to `order`.
+    ///
+    /// Works like [`fetch_concurrent`](#method.fetch_concurrent), without collecting the pages:
+    /// the first page is requested on its own to learn the total amount of posts, and is always
+    /// yielded first. The stream ends after the first error.
+    ///
+    /// [Legacy](../struct.ClientBuilder.html#method.legacy_api) sites don't report the total
+    /// along with a page, so it's [counted](#method.count) separately; without the `xml` feature
+    /// pages are instead requested one at a time until one comes back short.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, FetchOrder, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// use futures::StreamExt;
+    ///
+    /// let client = Client::public();
+    /// let mut pages = posts()
+    ///     .tags(&["step_arts"])
+    ///     .fetch_pages(&client, 4, FetchOrder::Unordered);
+    ///
+    /// while let Some(page) = pages.next().await {
+    ///     println!("{} posts", page?.posts.len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn fetch_pages<'c>(
+        self,
+        client: &'c Client,
+        concurrency: usize,
+        order: FetchOrder,
+    ) -> BoxStream<'c, Result<PostQuery, Error>> {
+        let builder = self.into_owned();
+        let page_size = builder.page_size();
+        let first_pid = builder.pid.unwrap_or(0);
+
+        let mut first = builder.clone();
+        first.pid = Some(first_pid);
+        let first = futures::stream::once(async move {
+            let mut page = first.send(client).await?;
+            if !page.legacy {
+                let total = page.attributes.count;
+                return Ok((page, Some(total)));
+            }
+            // legacy sites only guess the count one page ahead, so the real one is asked for
+            // separately, or pages are requested one after another until one comes back short
+            match first.count(client).await {
+                Ok(total) => {
+                    page.attributes.count = total;
+                    Ok((page, Some(total)))
+                }
+                Err(Error::CountUnavailable) => Ok((page, None)),
+                Err(err) => Err(err),
+            }
+        });
+        first
+            .flat_map(move |first| {
+                let rest = match &first {
+                    Ok((page, Some(total))) if page_size > 0 && page.received() > 0 => {
+                        let total = *total;
+                        let end = total.min(MAX_PAGINATION_OFFSET);
+                        let pids = (first_pid + 1..).take_while(move |pid| pid * page_size < end);
+                        let builder = builder.clone();
+                        let requests = futures::stream::iter(pids).map(move |pid| {
+                            let mut builder = builder.clone();
+                            builder.pid = Some(pid);
+                            async move {
+                                let mut page = builder.send(client).await?;
+                                if page.legacy {
+                                    page.attributes.count = total;
+                                }
+                                Ok(page)
+                            }
+                        });
+                        match order {
+                            FetchOrder::Ordered => requests.buffered(concurrency.max(1)).boxed(),
+                            FetchOrder::Unordered => {
+                                requests.buffer_unordered(concurrency.max(1)).boxed()
+                            }
+                        }
+                    }
+                    Ok((page, None)) if page_size > 0 && !page.is_exhausted() => {
+                        let builder = builder.clone();
+                        futures::stream::unfold(Some(first_pid + 1), move |pid| {
+                            let mut builder = builder.clone();
+                            async move {
+                                let pid =
+                                    pid.filter(|pid| pid * page_size < MAX_PAGINATION_OFFSET)?;
+                                builder.pid = Some(pid);
+                                let page = builder.send(client).await;
+                                let next = match &page {
+                                    Ok(page) if !page.is_exhausted() => Some(pid + 1),
+                                    _ => None,
+                                };
+                                Some((page, next))
+                            }
+                        })
+                        .boxed()
+                    }
+                    _ => futures::stream::empty().boxed(),
+                };
+                let first = first.map(|(page, _)| page);
+                futures::stream::once(futures::future::ready(first)).chain(rest)
+            })
+            .scan(false, |failed, page| {
+                let page = if *failed { None } else { Some(page) };
+                *failed = matches!(page, Some(Err(_)));
+                futures::future::ready(page)
+            })
+            .boxed()
+    }
+
+    /// Fetch posts matching the request one page at a time, until there are none left or
+    /// `max_posts` were collected.
+    ///
+    /// See [`CollectedPosts::is_complete`](struct.CollectedPosts.html#method.is_complete) to tell
+    /// whether everything was fetched.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// let collected = posts()
+    ///     .tags(&["step_arts"])
+    ///     .fetch_all(&client, 500)
+    ///     .await?;
+    ///
+    /// println!("got {} of {} posts", collected.posts.len(), collected.total);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn fetch_all(
+        self,
+        client: &Client,
+        max_posts: usize,
+    ) -> Result<CollectedPosts, Error> {
+        let page_size = self.page_size();
+        let mut pid = self.pid.unwrap_or(0);
+        let offset = pid * page_size;
+        let mut total = 0;
+        let mut posts = Vec::new();
+        let mut blacklisted = 0;
+        let mut deadline_exceeded = false;
+        let mut cancelled = false;
+
+        while posts.len() < max_posts && pid * page_size < MAX_PAGINATION_OFFSET {
+            let mut builder = self.clone();
+            builder.pid = Some(pid);
+            let page = match builder.send(client).await {
+                Ok(page) => page,
+                Err(Error::DeadlineExceeded) => {
+                    deadline_exceeded = true;
+                    break;
+                }
+                Err(Error::Cancelled) => {
+                    cancelled = true;
+                    break;
+                }
+                Err(err) => return Err(err),
+            };
+
+            total = page.attributes.count;
+            let exhausted = page.is_exhausted();
+            blacklisted += page.blacklisted.len();
+            posts.extend(page.posts);
+            if exhausted || page_size == 0 {
+                break;
+            }
+            pid += 1;
+        }
+
+        posts.truncate(max_posts);
+        Ok(CollectedPosts {
+            total,
+            offset,
+            posts,
+            blacklisted,
+            deadline_exceeded,
+            cancelled,
+        })
+    }
+
+    /// Page through every post matching the request.
+    ///
+    /// Pages of [`limit`](#method.limit) posts are requested as the stream is consumed.
+    /// Note that Gelbooru refuses to page further than 20,000 posts into a search.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// use futures::StreamExt;
+    ///
+    /// let client = Client::public();
+    /// let mut stream = posts()
+    ///     .tags(&["hatsune_miku", "solo"])
+    ///     .into_stream(&client)
+    ///     .lookahead(1); // fetch the next page while this one is processed
+    ///
+    /// while let Some(post) = stream.next().await {
+    ///     println!("{}", post?.image_url());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_stream(self, client: &'a Client) -> PostStream<'a> {
+        PostStream::new(client, self)
+    }
+
+    /// Send the request, returning `None` when no posts matched.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// match posts().tags(&["hatsune_miku", "kagamine_len"]).send_nonempty(&client).await? {
+    ///     Some(query) => println!("{} posts", query.posts.len()),
+    ///     None => println!("nothing found"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_nonempty(self, client: &Client) -> Result<Option<PostQuery>, Error> {
+        let query = self.send(client).await?;
+        Ok(if query.is_empty() { None } else { Some(query) })
+    }
+
+    /// Send the request, then resolve every distinct tag of the returned posts through the Tags
+    /// endpoint, so each tag carries its [`TagType`](enum.TagType.html) and count.
+    ///
+    /// Costs one request for the posts, plus one for every 100 distinct tags.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, TagType, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// let query = posts()
+    ///     .limit(10)
+    ///     .tags(&["hatsune_miku"])
+    ///     .enriched(&client)
+    ///     .await?;
+    ///
+    /// for post in query.posts {
+    ///     let artists = post.tags.iter().filter(|tag| tag.tag_type() == TagType::Artist);
+    ///     println!("{}: {} artists", post.post.id(), artists.count());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn enriched(self, client: &Client) -> Result<EnrichedPostQuery, Error> {
+        let options = self.options.clone();
+        let query = self.send(client).await?;
+
+        let mut names: Vec<&str> = query
+            .posts
+            .iter()
+            .flat_map(|post| post.tags())
+            .filter(|name| !name.is_empty())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+
+        let mut resolved: HashMap<String, Tag> = HashMap::with_capacity(names.len());
+        for batch in names.chunks(TAG_BATCH_SIZE) {
+            let mut tags = crate::tags();
+            tags.options = options.clone();
+            let tags = tags.names(client, batch).await?;
+            for tag in tags.tags {
+                resolved.insert(tag.name.clone(), tag);
+            }
+        }
+
+        let posts = query
+            .posts
+            .into_iter()
+            .map(|post| {
+                let tags = post
+                    .tags()
+                    .into_iter()
+                    .filter_map(|name| resolved.get(name).cloned())
+                    .collect();
+                EnrichedPost { post, tags }
+            })
+            .collect();
+
+        Ok(EnrichedPostQuery {
+            attributes: query.attributes,
+            posts,
+        })
+    }
+}
+
+/// Parent/child relations of posts, as discovered from a starting post.
+///
+/// See the [`relations`](../fn.relations.html) function.
+#[derive(Clone, Debug)]
+pub struct RelationGraph {
+    pub root: u64,
+    pub posts: HashMap<u64, Post>,
+    /// `(parent, child)` pairs.
+    pub edges: Vec<(u64, u64)>,
+    /// Whether the depth limit, request budget, deadline or cancellation stopped the search
+    /// before all relations were followed.
+    pub truncated: bool,
+}
+
+impl RelationGraph {
+    pub fn parent_of(&self, id: u64) -> Option<&Post> {
+        self.edges
+            .iter()
+            .find(|(_, child)| *child == id)
+            .and_then(|(parent, _)| self.posts.get(parent))
+    }
+
+    pub fn children_of(&self, id: u64) -> Vec<&Post> {
+        self.edges
+            .iter()
+            .filter(|(parent, _)| *parent == id)
+            .filter_map(|(_, child)| self.posts.get(child))
+            .collect()
+    }
+}
+
+/// Request builder for relation graphs.
+///
+/// See the [`relations`](../fn.relations.html) function for proper usage.
+#[derive(Clone, Debug)]
+pub struct RelationsRequestBuilder {
+    pub(crate) post_id: u64,
+    pub(crate) max_depth: usize,
+    pub(crate) max_requests: usize,
+    pub(crate) options: RequestOptions,
+}
+
+impl RelationsRequestBuilder {
+    /// How many parent/child links to follow away from the starting post.
+    ///
+    /// Defaults to 3.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Maximum amount of requests to send while building the graph.
+    ///
+    /// Defaults to 20.
+    pub fn max_requests(mut self, requests: usize) -> Self {
+        self.max_requests = requests;
+        self
+    }
+
+    request_options!();
+
+    // every request of the search shares the options, so a deadline or cancellation covers it
+    // as a whole
+    fn posts(&self) -> PostsRequestBuilder<'static> {
+        let mut builder = crate::posts();
+        builder.options = self.options.clone();
+        builder
+    }
+
+    /// Build the graph.
+    ///
+    /// If the starting post doesn't exist, the graph is empty. Once the starting post is found,
+    /// a [deadline](#method.deadline) or [cancellation](#method.cancel_on) keeps the relations
+    /// found so far, marking the graph as truncated.
+    pub async fn send(self, client: &Client) -> Result<RelationGraph, Error> {
+        let mut graph = RelationGraph {
+            root: self.post_id,
+            posts: HashMap::new(),
+            edges: Vec::new(),
+            truncated: false,
+        };
+        if self.max_requests == 0 {
+            graph.truncated = true;
+            return Ok(graph);
+        }
+
+        let mut requests = 1;
+        let root = self
+            .posts()
+            .tag(format!("id:{}", self.post_id))
+            .limit(1)
+            .send(client)
+            .await?;
+        match root.posts.into_iter().next() {
+            Some(post) => graph.posts.insert(post.id, post),
+            None => return Ok(graph),
+        };
+
+        let mut edges = HashSet::new();
+        let mut queue = VecDeque::from(vec![(self.post_id, 0)]);
+        'search: while let Some((id, depth)) = queue.pop_front() {
+            if depth >= self.max_depth {
+                // only relations that were left unfollowed make the graph incomplete
+                let post = &graph.posts[&id];
+                let unfetched_parent = post
+                    .parent_id
+                    .is_some_and(|parent| !graph.posts.contains_key(&parent));
+                if unfetched_parent || post.has_children != Some(false) {
+                    graph.truncated = true;
+                }
+                continue;
+            }
+
+            if let Some(parent) = graph.posts[&id].parent_id {
+                if !graph.posts.contains_key(&parent) {
+                    if requests >= self.max_requests {
+                        graph.truncated = true;
+                        break;
+                    }
+                    requests += 1;
+                    let query = self
+                        .posts()
+                        .tag(format!("id:{}", parent))
+                        .limit(1)
+                        .send(client)
+                        .await;
+                    let query = match query {
+                        Ok(query) => query,
+                        Err(Error::DeadlineExceeded | Error::Cancelled) => {
+                            graph.truncated = true;
+                            break 'search;
+                        }
+                        Err(err) => return Err(err),
+                    };
+                    if let Some(post) = query.posts.into_iter().next() {
+                        graph.posts.insert(post.id, post);
+                        queue.push_back((parent, depth + 1));
+                    }
+                }
+                if graph.posts.contains_key(&parent) {
+                    edges.insert((parent, id));
+                }
+            }
+
+            if graph.posts[&id].has_children == Some(false) {
+                continue;
+            }
+            for pid in 0..MAX_PAGINATION_OFFSET / MAX_LIMIT {
+                if requests >= self.max_requests {
+                    graph.truncated = true;
+                    break 'search;
+                }
+                requests += 1;
+                let children = self
+                    .posts()
+                    .tag(format!("parent:{}", id))
+                    .limit(MAX_LIMIT)
+                    .pid(pid)
+                    .send(client)
+                    .await;
+                let children = match children {
+                    Ok(children) => children,
+                    Err(Error::DeadlineExceeded | Error::Cancelled) => {
+                        graph.truncated = true;
+                        break 'search;
+                    }
+                    Err(err) => return Err(err),
+                };
+                let exhausted = children.posts.len() < MAX_LIMIT;
+                for child in children.posts.into_iter().filter(|post| post.id != id) {
+                    edges.insert((id, child.id));
+                    graph.posts.entry(child.id).or_insert_with(|| {
+                        queue.push_back((child.id, depth + 1));
+                        child
+                    });
+                }
+                if exhausted {
+                    break;
+                }
+            }
+        }
+
+        graph.edges = edges.into_iter().collect();
+        graph.edges.sort_unstable();
+        Ok(graph)
+    }
+}
+
+/// Tag on Gelbooru
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[non_exhaustive]
+pub struct Tag {
+    #[serde(deserialize_with = "crate::de::number")]
+    pub id: u64,
+    pub name: String,
+    #[serde(deserialize_with = "crate::de::number")]
+    pub count: u64,
+    #[serde(rename = "type", deserialize_with = "crate::de::number")]
+    pub tag_type: u64,
+    #[serde(deserialize_with = "crate::de::flag")]
+    pub ambiguous: u64,
+    /// Fields not covered by the ones above.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl ApiQuery for TagQuery {
+    #[cfg(feature = "xml")]
+    const ITEM: &'static str = "tag";
+
+    fn keep_raw(&mut self, body: Bytes) {
+        self.raw = Some(body);
+    }
+}
+
+impl TagQuery {
+    /// Body of the response this page was parsed from, if the client
+    /// [keeps them](../struct.Client.html#method.keep_raw_responses).
+    pub fn raw(&self) -> Option<&[u8]> {
+        self.raw.as_deref()
+    }
+}
+
+impl Tag {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    #[deprecated(since="0.3.5", note="Use tag.name() instead")]
+    pub fn tag(&self) -> &str {
+        self.name()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Type of the tag.
+    ///
+    /// Non-standard types, as some Gelbooru-based sites have, are treated as general
+    /// [`Tag`](enum.TagType.html#variant.Tag)s.
+    pub fn tag_type(&self) -> TagType {
+        self.try_tag_type().unwrap_or(TagType::Tag)
+    }
+
+    /// Type of the tag, failing with [`Error::UnexpectedValue`](../enum.Error.html) for
+    /// non-standard types.
+    pub fn try_tag_type(&self) -> Result<TagType, Error> {
+        use TagType::*;
+        match self.tag_type {
+            1 => Ok(Artist),
+            4 => Ok(Character),
+            3 => Ok(Copyright),
+            2 => Ok(Deprecated),
+            5 => Ok(Metadata),
+            0 => Ok(Tag),
+            _ => Err(Error::UnexpectedValue {
+                field: "tag type",
+                value: self.tag_type.to_string(),
+            }),
+        }
+    }
+
+    #[deprecated(since="0.3.5", note="Use tag.ambiguous() instead")]
+    pub fn ambigious(&self) -> bool {
+        self.ambiguous()
+    }
+
+    pub fn ambiguous(&self) -> bool {
+        self.ambiguous != 0
+    }
+}
+
+/// The type of a tag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TagType {
+    Artist,
+    Character,
+    Copyright,
+    Deprecated,
+    Metadata,
+    Tag,
+}
+
+/// Determines what field sorts tags in a query.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Ordering {
+    Date,
+    Count,
+    Name,
+}
+
+/// Request builder for the Tags endpoint.
+///
+/// See the [`tags`](fn.tags.html) function for proper usage.
+#[derive(Clone, Debug)]
+pub struct TagsRequestBuilder {
+    pub(crate) limit: Option<usize>,
+    pub(crate) after_id: Option<usize>,
+    pub(crate) order_by: Option<Ordering>,
+    pub(crate) ascending: Option<bool>,
+    pub(crate) options: RequestOptions,
+}
+
+enum TagSearch<'a> {
+    Name(&'a str),
+    Names(Vec<&'a str>),
+    Pattern(&'a str),
+}
+
+impl TagsRequestBuilder {
+    pub(crate) fn new() -> Self {
+        Self {
+            limit: None,
+            after_id: None,
+            order_by: None,
+            ascending: None,
+            options: Default::default(),
+        }
+    }
+
+    /// Amount of tags to recieve.
+    ///
+    /// When unspecified, default limit is 100, as set by the server.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn after_id(mut self, id: usize) -> Self {
+        self.after_id = Some(id);
+        self
+    }
+
+    pub fn ascending(mut self, ascending: bool) -> Self {
+        self.ascending = Some(ascending);
+        self
+    }
+
+    /// How tags are sorted.
+    /// _Date, Count, Name._
+    ///
+    /// This is mainly useful with [`send`](#method.send), but ordering works with the other search
+    /// methods as well ([`name`](#method.name), [`names`](#method.names), [`pattern`](#method.pattern))
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, Ordering, tags};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// tags()
+    ///     .limit(5)                 // 5 tags
+    ///     .ascending(true)             // descending
+    ///     .order_by(Ordering::Date) // according to creation-time
+    ///     .send(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn order_by(mut self, ordering: Ordering) -> Self {
+        self.order_by = Some(ordering);
+        self
+    }
+
+    request_options!();
+
+    /// Query for tags without name/pattern specifier.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, Ordering, tags};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// tags()
+    ///     .limit(10)                 // 10 tags
+    ///     .ascending(false)             // descending
+    ///     .order_by(Ordering::Count) // according to count (how many posts have tag)
+    ///     .send(&client)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send(self, client: &Client) -> Result<TagQuery, Error> {
+        self.search(client, None).await
+    }
+
+    /// Walk through every tag, oldest first, requesting pages as the stream is consumed.
+    ///
+    /// Pages are requested with [`after_id`](#method.after_id) set to the last tag received, so
+    /// any ordering set on the builder is replaced by creation order.
+    /// The [`limit`](#method.limit) sets the page size, up to the 100 tags the API returns at once.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, tags};
+    /// # async fn example() -> Result<(), Error> {
+    /// use futures::StreamExt;
+    ///
+    /// let client = Client::public();
+    /// let mut stream = tags().stream(&client);
+    ///
+    /// while let Some(tag) = stream.next().await {
+    ///     println!("{}", tag?.name());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn stream(self, client: &Client) -> TagStream<'_> {
+        TagStream::new(client, self)
+    }
+
+    /// Download every tag, in pages as large as the API allows.
+    ///
+    /// There are hundreds of thousands of tags, so this takes a while; see
+    /// [`fetch_all_with_progress`](#method.fetch_all_with_progress) for keeping track.
+    pub async fn fetch_all(self, client: &Client) -> Result<Vec<Tag>, Error> {
+        self.fetch_all_with_progress(client, |_| {}).await
+    }
+
+    /// Download every tag like [`fetch_all`](#method.fetch_all), calling `progress` with the
+    /// amount of tags downloaded after every page.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, tags};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// let all = tags()
+    ///     .fetch_all_with_progress(&client, |fetched| eprintln!("{} tags so far", fetched))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn fetch_all_with_progress<F: FnMut(usize)>(
+        mut self,
+        client: &Client,
+        mut progress: F,
+    ) -> Result<Vec<Tag>, Error> {
+        self.limit = Some(TAG_BATCH_SIZE);
+
+        let mut tags = Vec::new();
+        let mut stream = self.stream(client);
+        while let Some(tag) = stream.next().await {
+            tags.push(tag?);
+            if tags.len() % TAG_BATCH_SIZE == 0 {
+                progress(tags.len());
+            }
+        }
+
+        if tags.len() % TAG_BATCH_SIZE != 0 {
+            progress(tags.len());
+        }
+        Ok(tags)
+    }
+
+    /// Pull data for given tag
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, Ordering, tags};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// tags()
+    ///     .name(&client, "solo")
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    pub async fn name<S: AsRef<str>>(self, client: &Client, name: S) -> Result<Option<Tag>, Error> {
+        let search = TagSearch::Name(name.as_ref());
+        self.search(client, Some(search))
+            .await
+            .map(|tags| tags.tags.into_iter().next())
+    }
+
+    /// Pull data for the specified tags
+    ///
+    /// Tag limit is automatically set to accompany all the names.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, Ordering, tags};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// tags()
+    ///     .names(&client, &["solo", "hatsune_miku"])
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    pub async fn names<S: AsRef<str>>(
+        self,
+        client: &Client,
+        names: &[S],
+    ) -> Result<TagQuery, Error> {
+        let names = names.iter().map(|name| name.as_ref()).collect();
+        let search = TagSearch::Names(names);
+        self.search(client, Some(search)).await
+    }
+
+    /// Search for tags with a pattern.
+    ///
+    /// Use `_` for single-character wildcards and `%` for multi-character wildcards.
+    /// (`%choolgirl%` would act as `*choolgirl*` wildcard search)
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, Ordering, tags};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// tags()
+    ///     .limit(10)
+    ///     .pattern(&client, "%o_o") // matches regex /.*o.o/
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    pub async fn pattern<S: AsRef<str>>(
+        self,
+        client: &Client,
+        pattern: S,
+    ) -> Result<TagQuery, Error> {
+        let search = TagSearch::Pattern(pattern.as_ref());
+        self.search(client, Some(search)).await
+    }
+
+    /// Suggest existing tags for a possibly misspelled one.
+    ///
+    /// Candidates are gathered with a few [`pattern`](#method.pattern) searches around the name,
+    /// then ranked by edit distance and post count. An exact match always ranks first.
+    /// The [`limit`](#method.limit) caps the amount of suggestions, which defaults to 5.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, tags};
+    /// # async fn example() -> Result<(), Error> {
+    /// # let client = Client::public();
+    /// let suggestions = tags()
+    ///     .suggest(&client, "hatsune_mike")
+    ///     .await?;
+    ///
+    /// if let Some(tag) = suggestions.first() {
+    ///     println!("did you mean {}?", tag.name());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn suggest<S: AsRef<str>>(self, client: &Client, name: S) -> Result<Vec<Tag>, Error> {
+        let name = name.as_ref().to_lowercase();
+        let suggestions = self.limit.unwrap_or(5);
+
+        let mut candidates: HashMap<u64, Tag> = HashMap::new();
+        for pattern in suggestion_patterns(&name) {
+            let mut builder = self.clone();
+            builder.limit = Some(TAG_BATCH_SIZE);
+            builder.order_by = Some(Ordering::Count);
+            builder.ascending = Some(false);
+
+            let tags = builder.pattern(client, pattern).await?;
+            for tag in tags.tags {
+                candidates.entry(tag.id).or_insert(tag);
+            }
+        }
+
+        let max_distance = (name.chars().count() / 3).max(2);
+        let mut ranked: Vec<(usize, Tag)> = candidates
+            .into_values()
+            .map(|tag| (edit_distance(&name, &tag.name), tag))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect();
+        ranked.sort_by(|(distance_a, a), (distance_b, b)| {
+            distance_a.cmp(distance_b).then(b.count.cmp(&a.count))
+        });
+
+        Ok(ranked
+            .into_iter()
+            .take(suggestions)
+            .map(|(_, tag)| tag)
+            .collect())
+    }
+
     async fn search(
         self,
         client: &Client,
@@ -549,6 +3328,23 @@ impl TagsRequestBuilder {
             }
         });
 
+        let qs = self.query_strings(limit, search);
+        query_api(client, qs, &self.options).await
+    }
+
+    /// URL the request would be sent to, without sending it.
+    ///
+    /// Like [`send`](#method.send), this lists tags without searching for any by name.
+    /// Credentials set with [`auth`](#method.auth) are included, but those of the client's
+    /// [`AuthPool`](../struct.AuthPool.html) aren't, as which key is used is only decided when
+    /// sending.
+    pub fn build_url(&self, client: &Client) -> Result<hyper::Uri, Error> {
+        let qs = self.query_strings(self.limit.unwrap_or(100), None);
+        let (uri, _) = request_uri::<TagQuery>(client, qs, self.options.auth.clone().flatten())?;
+        Ok(uri)
+    }
+
+    fn query_strings<'a>(&self, limit: usize, search: Option<TagSearch<'_>>) -> QueryStrings<'a> {
         let mut qs: QueryStrings = Default::default();
         qs.insert("s", "tag".to_string());
         qs.insert("limit", limit.to_string());
@@ -575,59 +3371,878 @@ impl TagsRequestBuilder {
         if let Some(search) = search {
             use TagSearch::*;
             let (mode, mode_value) = match search {
-                Name(name) => ("name", name.to_string()),
-                Names(names) => ("names", names.join("+")),
-                Pattern(pattern) => ("name_pattern", pattern.to_string()),
+                Name(name) => ("name", encode(name)),
+                Names(names) => {
+                    let names: Vec<String> = names.iter().map(|name| encode(name)).collect();
+                    ("names", names.join("+"))
+                }
+                // `%` wildcards have to reach the server as they are, not as escapes
+                Pattern(pattern) => ("name_pattern", encode(pattern)),
             };
             qs.insert(mode, mode_value);
         }
 
-        query_api(client, qs).await
+        qs
+    }
+}
+
+// pattern searches likely to turn up the intended tag for a misspelled name
+fn suggestion_patterns(name: &str) -> Vec<String> {
+    const MAX_PATTERNS: usize = 5;
+
+    let chars: Vec<char> = name.chars().collect();
+    let (head, tail) = chars.split_at(chars.len() / 2);
+    let mut patterns = vec![
+        format!("{}%", head.iter().collect::<String>()),
+        format!("%{}", tail.iter().collect::<String>()),
+    ];
+    patterns.extend(
+        name.split('_')
+            .filter(|word| word.chars().count() >= 3)
+            .map(|word| format!("%{}%", word)),
+    );
+
+    let mut seen = HashSet::new();
+    patterns.retain(|pattern| pattern.len() > 1 && seen.insert(pattern.clone()));
+    patterns.truncate(MAX_PATTERNS);
+    patterns
+}
+
+// levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + if ca == *cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Comments on posts. Requires the `xml` feature.
+#[cfg(feature = "xml")]
+#[derive(Deserialize, Serialize, Debug)]
+#[non_exhaustive]
+pub struct CommentQuery {
+    #[serde(
+        rename = "comment",
+        default = "Vec::new",
+        deserialize_with = "crate::de::one_or_many"
+    )]
+    pub comments: Vec<Comment>,
+}
+
+#[cfg(feature = "xml")]
+impl ApiQuery for CommentQuery {
+    const ITEM: &'static str = "comment";
+    const XML_ONLY: bool = true;
+}
+
+/// Comment on a post
+#[cfg(feature = "xml")]
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[non_exhaustive]
+pub struct Comment {
+    #[serde(deserialize_with = "crate::de::number")]
+    pub id: u64,
+    #[serde(deserialize_with = "crate::de::number")]
+    pub post_id: u64,
+    pub body: String,
+    pub creator: String,
+    #[serde(deserialize_with = "crate::de::number")]
+    pub creator_id: u64,
+    pub created_at: String,
+    /// Fields not covered by the ones above.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+#[cfg(feature = "xml")]
+impl Comment {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn post_id(&self) -> u64 {
+        self.post_id
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    pub fn creator(&self) -> &str {
+        &self.creator
+    }
+
+    pub fn creator_id(&self) -> u64 {
+        self.creator_id
+    }
+
+    pub fn created_at_raw(&self) -> &str {
+        &self.created_at
     }
 }
 
-/*
- * @TODO: add support for reading XML, since Comments & Deleted Images APIs don't support
- * outputting in json.
+/// Builder for [`comments`](../fn.comments.html) requests. Requires the `xml` feature.
+#[cfg(feature = "xml")]
+#[derive(Clone, Debug)]
+pub struct CommentsRequestBuilder {
+    pub(crate) post_id: Option<u64>,
+    pub(crate) options: RequestOptions,
+}
 
-#[derive(Deserialize, Debug)]
-pub struct Comment {}
+#[cfg(feature = "xml")]
+impl CommentsRequestBuilder {
+    /// Only comments on the given post.
+    pub fn post_id(mut self, post_id: u64) -> Self {
+        self.post_id = Some(post_id);
+        self
+    }
 
-impl ApiType for Comment {}
+    request_options!();
 
-pub async fn comments(client: &Client, post_id: u64) -> Result<Vec<Comment>, Error> {
+    pub async fn send(self, client: &Client) -> Result<CommentQuery, Error> {
         let mut qs: QueryStrings = Default::default();
         qs.insert("s", "comment".to_string());
-        qs.insert("post_id", post_id.to_string());
+        if let Some(post_id) = self.post_id {
+            qs.insert("post_id", post_id.to_string());
+        }
+
+        query_api(client, qs, &self.options).await
+    }
+}
 
-        query_api(client, qs).await
+/// Translation notes on posts. Requires the `xml` feature.
+#[cfg(feature = "xml")]
+#[derive(Deserialize, Serialize, Debug)]
+#[non_exhaustive]
+pub struct NoteQuery {
+    #[serde(
+        rename = "note",
+        default = "Vec::new",
+        deserialize_with = "crate::de::one_or_many"
+    )]
+    pub notes: Vec<Note>,
 }
-*/
 
-// internal function as to DRY
-async fn query_api<T: ApiQuery>(client: &Client, mut qs: QueryStrings<'_>) -> Result<T, Error> {
-    if let Some(auth) = &client.auth {
+#[cfg(feature = "xml")]
+impl ApiQuery for NoteQuery {
+    const ITEM: &'static str = "note";
+    const XML_ONLY: bool = true;
+}
+
+/// Translation note on a region of a post's image
+#[cfg(feature = "xml")]
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[non_exhaustive]
+pub struct Note {
+    #[serde(deserialize_with = "crate::de::number")]
+    pub id: u64,
+    #[serde(deserialize_with = "crate::de::number")]
+    pub post_id: u64,
+    #[serde(deserialize_with = "crate::de::number")]
+    pub x: u64,
+    #[serde(deserialize_with = "crate::de::number")]
+    pub y: u64,
+    #[serde(deserialize_with = "crate::de::number")]
+    pub width: u64,
+    #[serde(deserialize_with = "crate::de::number")]
+    pub height: u64,
+    pub body: String,
+    pub created_at: String,
+    /// Fields not covered by the ones above.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+#[cfg(feature = "xml")]
+impl Note {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn post_id(&self) -> u64 {
+        self.post_id
+    }
+
+    /// Position of the note's top left corner on the image.
+    pub fn position(&self) -> (u64, u64) {
+        (self.x, self.y)
+    }
+
+    pub fn dimensions(&self) -> (u64, u64) {
+        (self.width, self.height)
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    pub fn created_at_raw(&self) -> &str {
+        &self.created_at
+    }
+}
+
+/// Builder for [`notes`](../fn.notes.html) requests. Requires the `xml` feature.
+#[cfg(feature = "xml")]
+#[derive(Clone, Debug)]
+pub struct NotesRequestBuilder {
+    pub(crate) post_id: Option<u64>,
+    pub(crate) options: RequestOptions,
+}
+
+#[cfg(feature = "xml")]
+impl NotesRequestBuilder {
+    /// Only notes on the given post.
+    pub fn post_id(mut self, post_id: u64) -> Self {
+        self.post_id = Some(post_id);
+        self
+    }
+
+    request_options!();
+
+    pub async fn send(self, client: &Client) -> Result<NoteQuery, Error> {
+        let mut qs: QueryStrings = Default::default();
+        qs.insert("s", "note".to_string());
+        if let Some(post_id) = self.post_id {
+            qs.insert("post_id", post_id.to_string());
+        }
+
+        query_api(client, qs, &self.options).await
+    }
+}
+
+/// Images deleted from Gelbooru. Requires the `xml` feature.
+#[cfg(feature = "xml")]
+#[derive(Deserialize, Serialize, Debug)]
+#[non_exhaustive]
+pub struct DeletedImageQuery {
+    #[serde(
+        rename = "post",
+        default = "Vec::new",
+        deserialize_with = "crate::de::one_or_many"
+    )]
+    pub images: Vec<DeletedImage>,
+}
+
+#[cfg(feature = "xml")]
+impl ApiQuery for DeletedImageQuery {
+    const ITEM: &'static str = "post";
+    const XML_ONLY: bool = true;
+}
+
+/// Image deleted from Gelbooru
+#[cfg(feature = "xml")]
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[non_exhaustive]
+pub struct DeletedImage {
+    /// Id of the deleted post.
+    #[serde(deserialize_with = "crate::de::number")]
+    pub id: u64,
+    #[serde(default)]
+    pub md5: String,
+    /// Fields not covered by the ones above.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+#[cfg(feature = "xml")]
+impl DeletedImage {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// MD5 of the deleted file, which downloaded files are named after.
+    pub fn md5(&self) -> &str {
+        &self.md5
+    }
+}
+
+/// Builder for [`deleted_images`](../fn.deleted_images.html) requests. Requires the `xml` feature.
+#[cfg(feature = "xml")]
+#[derive(Clone, Debug)]
+pub struct DeletedImagesRequestBuilder {
+    pub(crate) last_id: Option<u64>,
+    pub(crate) options: RequestOptions,
+}
+
+#[cfg(feature = "xml")]
+impl DeletedImagesRequestBuilder {
+    /// Only images deleted from posts with a higher id than the given one.
+    pub fn last_id(mut self, last_id: u64) -> Self {
+        self.last_id = Some(last_id);
+        self
+    }
+
+    request_options!();
+
+    pub async fn send(self, client: &Client) -> Result<DeletedImageQuery, Error> {
+        let mut qs: QueryStrings = Default::default();
+        qs.insert("s", "deleted_image".to_string());
+        if let Some(last_id) = self.last_id {
+            qs.insert("last_id", last_id.to_string());
+        }
+
+        query_api(client, qs, &self.options).await
+    }
+}
+
+/// Suggestion of Gelbooru's search box autocompletion
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[non_exhaustive]
+pub struct Suggestion {
+    /// Text to display, such as `hatsune_miku`.
+    pub label: String,
+    /// Text to put into the search.
+    pub value: String,
+    #[serde(default, deserialize_with = "crate::de::number")]
+    pub post_count: u64,
+    /// Category as named by Gelbooru, such as `character`.
+    #[serde(default)]
+    pub category: String,
+    /// Fields not covered by the ones above.
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl Suggestion {
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn post_count(&self) -> u64 {
+        self.post_count
+    }
+
+    /// Type of the suggested tag, if the category is a known one.
+    pub fn tag_type(&self) -> Option<TagType> {
+        use TagType::*;
+        match self.category.as_str() {
+            "artist" => Some(Artist),
+            "character" => Some(Character),
+            "copyright" => Some(Copyright),
+            "deprecated" => Some(Deprecated),
+            "metadata" => Some(Metadata),
+            "tag" | "general" => Some(Tag),
+            _ => None,
+        }
+    }
+}
+
+// the autocompletion endpoint responds with a bare list
+#[derive(Deserialize)]
+#[serde(transparent)]
+struct Suggestions(Vec<Suggestion>);
+
+impl ApiQuery for Suggestions {
+    #[cfg(feature = "xml")]
+    const ITEM: &'static str = "suggestion";
+    #[cfg(feature = "xml")]
+    const JSON_ONLY: bool = true;
+    const PATH: &'static str = AUTOCOMPLETE_PATH;
+}
+
+pub(crate) async fn autocomplete(client: &Client, term: &str) -> Result<Vec<Suggestion>, Error> {
+    let mut qs: QueryStrings = Default::default();
+    qs.insert("term", encode(term));
+
+    match query_api::<Suggestions>(client, qs, &Default::default()).await {
+        Ok(Suggestions(suggestions)) => Ok(suggestions),
+        // nothing at all is sent back when there's nothing to suggest
+        Err(Error::EmptyResponse) => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Pool of posts, such as the pages of a comic, in order
+#[derive(Clone, Debug)]
+pub struct Pool {
+    pub id: u64,
+    pub name: String,
+    pub post_ids: Vec<u64>,
+}
+
+impl Pool {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Ids of the pool's posts, in the pool's order.
+    pub fn post_ids(&self) -> &[u64] {
+        &self.post_ids
+    }
+}
+
+/// Request builder for pools.
+///
+/// See the [`pools`](../fn.pools.html) function for proper usage.
+#[derive(Clone, Debug, Default)]
+pub struct PoolsRequestBuilder {
+    _private: (),
+}
+
+impl PoolsRequestBuilder {
+    /// Fetch a pool and its posts' ids.
+    ///
+    /// The API has no pools endpoint, so this reads the pool's web page.
+    pub async fn get(self, client: &Client, id: u64) -> Result<Pool, Error> {
+        let uri = format!("{}{}&id={}", client.base_url, POOL_PATH, id);
+        let body = fetch_page(client, &uri).await?;
+        let page = String::from_utf8_lossy(&body);
+
+        let post_ids = pool_post_ids(&page);
+        let name = pool_name(&page);
+        if post_ids.is_empty() && name.is_none() {
+            return Err(match unavailable_kind(&body) {
+                Some(kind) => Error::ServiceUnavailable { kind },
+                None => Error::Api {
+                    message: format!("no pool with id {}", id),
+                },
+            });
+        }
+
+        Ok(Pool {
+            id,
+            name: name.unwrap_or_default(),
+            post_ids,
+        })
+    }
+}
+
+// post links in page order, skipping repeats such as a thumbnail and its caption
+pub(crate) fn pool_post_ids(page: &str) -> Vec<u64> {
+    const LINK: &str = "s=view&amp;id=";
+
+    let mut seen = HashSet::new();
+    page.match_indices(LINK)
+        .filter_map(|(start, _)| {
+            let digits = &page[start + LINK.len()..];
+            let end = digits
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(digits.len());
+            digits[..end].parse().ok()
+        })
+        .filter(|id| seen.insert(*id))
+        .collect()
+}
+
+// heading of the pool's page, `<h3>Now Viewing: name</h3>`
+pub(crate) fn pool_name(page: &str) -> Option<String> {
+    const HEADING: &str = "Now Viewing:";
+
+    let start = page.find(HEADING)? + HEADING.len();
+    let end = start + page[start..].find('<')?;
+    let name = page[start..end]
+        .trim()
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#039;", "'")
+        .replace("&amp;", "&");
+    Some(name)
+}
+
+// plain GET outside of the API, for endpoints that aren't part of it
+async fn fetch_page(client: &Client, uri: &str) -> Result<hyper::body::Bytes, Error> {
+    let uri = uri.parse::<hyper::Uri>()?;
+
+    log_debug!("GET {}", uri);
+    let res = client.get_site(uri).await?;
+    let status = res.status();
+    let body = res.into_body();
+
+    if !status.is_success() {
+        return Err(match unavailable_kind(&body) {
+            Some(kind) => Error::ServiceUnavailable { kind },
+            None => Error::Status(status),
+        });
+    }
+    Ok(body)
+}
+
+// excluded tags given as `-tag` would otherwise be negated twice
+fn strip_negation(tag: Cow<'_, str>) -> Cow<'_, str> {
+    match tag {
+        Cow::Borrowed(tag) => Cow::Borrowed(tag.strip_prefix('-').unwrap_or(tag)),
+        Cow::Owned(tag) => match tag.strip_prefix('-') {
+            Some(stripped) => Cow::Owned(stripped.to_string()),
+            None => Cow::Owned(tag),
+        },
+    }
+}
+
+// percent-encodes everything but unreserved characters, for use in query strings
+pub(crate) fn encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+// reverses percent-encoding of query strings, where `+` also stands for a space
+pub(crate) fn decode(value: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut bytes = value.bytes();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            byte => decoded.push(byte),
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+// URL of a request and the format its response will be in
+fn request_uri<T: ApiQuery>(
+    client: &Client,
+    mut qs: QueryStrings<'_>,
+    auth: Option<AuthDetails>,
+) -> Result<(hyper::Uri, ResponseFormat), Error> {
+    if let Some(auth) = auth {
         qs.insert("user_id", auth.user.to_string());
-        qs.insert("api_key", auth.key.clone());
+        qs.insert("api_key", auth.key);
     }
 
+    let format = client.response_format;
+    #[cfg(feature = "xml")]
+    let format = match (T::XML_ONLY, T::JSON_ONLY) {
+        (true, _) => ResponseFormat::Xml,
+        (_, true) => ResponseFormat::Json,
+        _ => format,
+    };
+
+    let json = match format {
+        ResponseFormat::Json => "1",
+        #[cfg(feature = "xml")]
+        ResponseFormat::Xml => "0",
+    };
+    qs.insert("json", json.to_string());
+
     let query_string: String = qs
         .iter()
         .map(|(query, value)| format!("&{}={}", query, value))
         .collect();
 
-    let uri = format!("{}{}", API_BASE, query_string)
+    let uri = format!("{}{}{}", client.base_url, T::PATH, query_string)
         .parse::<hyper::Uri>()
-        .map_err(|err| Error::UriParse(err))?;
-
-    let res = client
-        .http_client
-        .get(uri)
-        .await
-        .map_err(|err| Error::Request(err))?;
-    let body = hyper::body::aggregate(res)
-        .await
-        .map_err(|err| Error::Request(err))?;
-
-    serde_json::from_reader(body.reader()).map_err(|err| Error::JsonDeserialize(err))
+        .map_err(Error::UriParse)?;
+
+    Ok((uri, format))
+}
+
+// internal function as to DRY
+async fn query_api<T: ApiQuery>(
+    client: &Client,
+    qs: QueryStrings<'_>,
+    options: &RequestOptions,
+) -> Result<T, Error> {
+    let request = async {
+        let mut retry = 0;
+        loop {
+            match send_query(client, qs.clone(), options).await {
+                Err(err) if retry < client.retries && err.is_transient() => {
+                    let delay = client.backoff.saturating_mul(2u32.saturating_pow(retry));
+                    log_debug!("{}, retrying in {:?}", err, delay);
+                    crate::runtime::sleep(delay).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    };
+    let request = async {
+        match options.timeout {
+            Some(timeout) => crate::runtime::timeout(timeout, request)
+                .await
+                .ok_or(Error::Timeout)?,
+            None => request.await,
+        }
+    };
+    let request = async {
+        match options.deadline {
+            Some(deadline) => crate::runtime::timeout_at(deadline, request)
+                .await
+                .ok_or(Error::DeadlineExceeded)?,
+            None => request.await,
+        }
+    };
+    match &options.cancel {
+        // dropping the request aborts it, including any connection in use
+        Some(signal) => {
+            use futures::future::{select, Either};
+
+            let cancelled = signal.0.clone();
+            futures::pin_mut!(cancelled, request);
+            // checked first, so an already completed signal doesn't send anything
+            match select(cancelled, request).await {
+                Either::Left(_) => Err(Error::Cancelled),
+                Either::Right((result, _)) => result,
+            }
+        }
+        None => request.await,
+    }
+}
+
+async fn send_query<T: ApiQuery>(
+    client: &Client,
+    qs: QueryStrings<'_>,
+    options: &RequestOptions,
+) -> Result<T, Error> {
+    // overridden credentials bypass the client's pool entirely
+    let pool = match &options.auth {
+        Some(_) => None,
+        None => client.auth.as_deref(),
+    };
+    let attempts = pool.map_or(1, |pool| pool.len().max(1));
+
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+
+        let key = match pool {
+            Some(pool) => pool.acquire()?,
+            None => None,
+        };
+        let auth = match &options.auth {
+            Some(auth) => auth.clone(),
+            None => key.as_ref().map(|(_, auth)| auth.clone()),
+        };
+        let (uri, format) = request_uri::<T>(client, qs.clone(), auth)?;
+
+        log_debug!("GET {}", redact(&uri));
+        let started = Instant::now();
+        let res = client.get_site(uri).await?;
+
+        let status = res.status();
+        log_debug!("{} after {:?}", status, started.elapsed());
+        if status == hyper::StatusCode::TOO_MANY_REQUESTS
+            || status == hyper::StatusCode::UNAUTHORIZED
+        {
+            if let (Some(pool), Some((index, _))) = (pool, &key) {
+                pool.penalize(*index);
+                if attempt < attempts {
+                    log_debug!("key {} penalized, retrying with the next key", index);
+                    continue;
+                }
+                log_debug!("key {} penalized, no keys left to retry with", index);
+            }
+        }
+
+        let content_type = res
+            .headers()
+            .get(hyper::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let mut body = res.into_body();
+        // Gelbooru 0.2 sites respond to searches without results with nothing at all
+        if client.legacy_api
+            && status.is_success()
+            && body.is_empty()
+            && matches!(format, ResponseFormat::Json)
+        {
+            body = hyper::body::Bytes::from_static(b"[]");
+        }
+
+        if let Some(kind) = unavailable_kind(&body) {
+            return Err(Error::ServiceUnavailable { kind });
+        }
+        if !status.is_success() {
+            return Err(Error::Status(status));
+        }
+        if let Some(got) = content_type {
+            if !is_api_content(&got, &body) {
+                // XML errors come labelled as web pages too
+                let markup = body.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'<');
+                let error = api_error(&body).filter(|_| markup);
+                return Err(error.unwrap_or(Error::UnexpectedContentType { got }));
+            }
+        }
+        if body.is_empty() {
+            return Err(Error::EmptyResponse);
+        }
+
+        log_trace!("response body: {}", String::from_utf8_lossy(&body));
+        let parsed = match format {
+            ResponseFormat::Json => T::from_json(&body),
+            #[cfg(feature = "xml")]
+            ResponseFormat::Xml => T::from_xml(&body),
+        };
+
+        // error payloads don't fit the response types, so they're only looked for on failure
+        let mut parsed = parsed.map_err(|err| match api_error(&body) {
+            Some(err) => err,
+            None if client.dump_failed_responses => dump_response(&body, err),
+            None => err,
+        })?;
+        if client.keep_raw_responses {
+            parsed.keep_raw(body);
+        }
+        parsed.set_site(&client.base_url);
+        return Ok(parsed);
+    }
+}
+
+// request URI with credentials masked, for logging
+fn redact(uri: &hyper::Uri) -> String {
+    let query = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name @ ("api_key" | "user_id"), _)) => format!("{}=<redacted>", name),
+            _ => pair.to_string(),
+        });
+    format!("{}?{}", uri.path(), query.collect::<Vec<_>>().join("&"))
+}
+
+// writes a response that failed to deserialize to a temporary file and attaches its path to the
+// error; see `Client::dump_failed_responses`
+fn dump_response(body: &[u8], err: Error) -> Error {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    let path = std::env::temp_dir().join(format!(
+        "gelbooru-api-{}-{}.response",
+        std::process::id(),
+        nanos
+    ));
+
+    match std::fs::write(&path, body) {
+        Ok(()) => Error::DumpedResponse {
+            source: Box::new(err),
+            path,
+        },
+        Err(_) => err,
+    }
+}
+
+// error message of a failed request, in either of the shapes the API reports them:
+// `{"success": false, "message": ...}`, `<response success="false" reason="..."/>`, or plain text
+pub(crate) fn api_error(body: &[u8]) -> Option<Error> {
+    let text = String::from_utf8_lossy(body);
+    let text = text.trim();
+
+    let message = if text.starts_with('{') {
+        let value: serde_json::Value = serde_json::from_str(text).ok()?;
+        match &value["success"] {
+            serde_json::Value::Bool(false) => {}
+            serde_json::Value::String(success) if success == "false" => {}
+            _ => return None,
+        }
+        ["message", "reason", "error"]
+            .iter()
+            .find_map(|key| value[*key].as_str())
+            .unwrap_or("request failed")
+            .to_string()
+    } else if text.starts_with('<') {
+        let response = &text[text.find("<response")?..];
+        if !response.contains(r#"success="false""#) {
+            return None;
+        }
+        let reason = &response[response.find(r#"reason=""#)? + 8..];
+        reason[..reason.find('"')?].to_string()
+    } else if !text.is_empty() {
+        text.to_string()
+    } else {
+        return None;
+    };
+
+    // the message has to be about a meta-tag being refused, not merely mention either
+    let lower = message.to_lowercase();
+    let meta_tag = ["meta-tag", "metatag", "meta tag"]
+        .iter()
+        .any(|name| lower.contains(name));
+    let refused = [
+        "restricted",
+        "not allowed",
+        "cannot",
+        "can't",
+        "can only",
+        "only available",
+    ]
+    .iter()
+    .any(|phrase| lower.contains(phrase));
+    Some(if lower.contains("too deep") {
+        Error::PaginationTooDeep { message }
+    } else if lower.contains("tags at once") || lower.contains("too many tags") {
+        Error::TooManyTags { message }
+    } else if meta_tag && refused {
+        Error::RestrictedMetaTag { message }
+    } else {
+        Error::Api { message }
+    })
+}
+
+// whether a response of the given content type can hold an API response; some engines label
+// their JSON as html, so html is only refused when the body looks like markup
+pub(crate) fn is_api_content(content_type: &str, body: &[u8]) -> bool {
+    const API_TYPES: &[&str] = &["json", "xml", "javascript", "text/plain"];
+
+    let content_type = content_type.to_lowercase();
+    if API_TYPES
+        .iter()
+        .any(|api_type| content_type.contains(api_type))
+    {
+        return true;
+    }
+
+    let first = body
+        .iter()
+        .copied()
+        .find(|byte| !byte.is_ascii_whitespace());
+    content_type.contains("html") && matches!(first, Some(b'{') | Some(b'['))
+}
+
+// recognizes Cloudflare challenges and maintenance pages served in place of API responses
+pub(crate) fn unavailable_kind(body: &[u8]) -> Option<UnavailableKind> {
+    const SNIFF_LENGTH: usize = 16 * 1024;
+    const CHALLENGE_MARKERS: &[&str] = &[
+        "just a moment...",
+        "attention required! | cloudflare",
+        "cf-browser-verification",
+        "challenge-platform",
+        "cf_chl_",
+    ];
+
+    let head = String::from_utf8_lossy(&body[..body.len().min(SNIFF_LENGTH)]).to_lowercase();
+    let head = head.trim_start();
+    if !head.starts_with("<!doctype html") && !head.starts_with("<html") {
+        return None;
+    }
+
+    if CHALLENGE_MARKERS.iter().any(|marker| head.contains(marker)) {
+        Some(UnavailableKind::Challenge)
+    } else if head.contains("maintenance") {
+        Some(UnavailableKind::Maintenance)
+    } else {
+        None
+    }
 }
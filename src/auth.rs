@@ -1,19 +1,261 @@
+use crate::runtime::Instant;
 use crate::Error;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
 
 /// Credentials for use with API
-#[derive(Clone, Debug)]
+///
+/// The key is masked when debug-printed, so `dbg!` output can be shared safely.
+#[derive(Clone)]
 pub struct AuthDetails {
     pub user: usize,
     pub key: String,
 }
 
+impl fmt::Debug for AuthDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthDetails")
+            .field("user", &self.user)
+            .field("key", &mask(&self.key))
+            .finish()
+    }
+}
+
+// first and last two characters only, or nothing at all of short keys
+pub(crate) fn mask(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    match chars.len() {
+        0 => String::new(),
+        len if len <= 8 => "…".to_string(),
+        len => format!(
+            "{}…{}",
+            chars[..2].iter().collect::<String>(),
+            chars[len - 2..].iter().collect::<String>()
+        ),
+    }
+}
+
 impl AuthDetails {
     pub fn from_query_string(qs: &str) -> Result<Self, Error> {
         let user_start = qs.find("&user_id=").ok_or(Error::ParseAuth)?;
         let user_raw = &qs[user_start + 9..];
-        let user = user_raw.parse().map_err(|err| Error::ParseUserId(err))?;
+        let user = user_raw.parse().map_err(Error::ParseUserId)?;
         let key = qs[9..user_start].to_string();
 
         Ok(Self { user, key })
     }
 }
+
+/// How an [`AuthPool`](struct.AuthPool.html) picks the credentials for a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeySelection {
+    /// Cycle through the credentials, one request each.
+    RoundRobin,
+    /// Stick to the first credentials that aren't cooling down.
+    Failover,
+}
+
+/// Set of credentials a client spreads its requests over.
+///
+/// Credentials that get rate-limited (`429`) or rejected (`401`) are put on cooldown, and the
+/// request is retried with the next available ones.
+///
+/// Requests are counted per credentials over a sliding window (see [`usage`](#method.usage)).
+/// With a [`soft_limit`](#method.soft_limit) set, credentials that reach it are paused until
+/// their window frees up.
+///
+/// ## Example
+/// ```rust
+/// # use gelbooru_api::{AuthDetails, AuthPool, Client, Error, KeySelection};
+/// # use std::time::Duration;
+/// # fn example() -> Result<(), Error> {
+/// let pool = AuthPool::new(vec![
+///     AuthDetails::from_query_string("&api_key=0123456789abcdef&user_id=1234")?,
+///     AuthDetails::from_query_string("&api_key=fedcba9876543210&user_id=5678")?,
+/// ])
+/// .selection(KeySelection::RoundRobin)
+/// .cooldown(Duration::from_secs(300));
+///
+/// let client = Client::with_auth_pool(pool);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AuthPool {
+    keys: Vec<AuthDetails>,
+    selection: KeySelection,
+    cooldown: Duration,
+    window: Duration,
+    soft_limit: Option<usize>,
+    state: Mutex<PoolState>,
+}
+
+#[derive(Debug)]
+struct PoolState {
+    next: usize,
+    cooldown_until: Vec<Option<Instant>>,
+    // send times of requests within the window
+    requests: Vec<VecDeque<Instant>>,
+}
+
+/// Request statistics of a single set of credentials in an [`AuthPool`](struct.AuthPool.html).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyUsage {
+    pub user: usize,
+    /// Requests sent within the pool's window.
+    pub requests: usize,
+    /// Whether the soft limit has been reached.
+    pub paused: bool,
+    /// Whether the credentials were recently rate-limited or rejected.
+    pub cooling_down: bool,
+}
+
+impl AuthPool {
+    /// Pool using round-robin selection, a one minute cooldown, and a one hour window without a
+    /// soft limit.
+    pub fn new(keys: Vec<AuthDetails>) -> Self {
+        let state = PoolState {
+            next: 0,
+            cooldown_until: vec![None; keys.len()],
+            requests: vec![VecDeque::new(); keys.len()],
+        };
+
+        Self {
+            keys,
+            selection: KeySelection::RoundRobin,
+            cooldown: Duration::from_secs(60),
+            window: Duration::from_secs(60 * 60),
+            soft_limit: None,
+            state: Mutex::new(state),
+        }
+    }
+
+    pub fn selection(mut self, selection: KeySelection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// How long credentials are skipped after being rate-limited or rejected.
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Sliding window requests are counted over.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Maximum amount of requests per credentials within the window.
+    ///
+    /// Once every set of credentials has reached it, requests fail with
+    /// [`Error::QuotaExhausted`](enum.Error.html#variant.QuotaExhausted).
+    pub fn soft_limit(mut self, requests: usize) -> Self {
+        self.soft_limit = Some(requests);
+        self
+    }
+
+    /// Request statistics for every set of credentials, in the order they were added.
+    pub fn usage(&self) -> Vec<KeyUsage> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        self.expire(&mut state, now);
+
+        self.keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| {
+                let requests = state.requests[index].len();
+                KeyUsage {
+                    user: key.user,
+                    requests,
+                    paused: self.soft_limit.is_some_and(|limit| requests >= limit),
+                    cooling_down: state.cooldown_until[index].is_some_and(|until| until > now),
+                }
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    // picks credentials for the next request and counts it; when all are cooling down, the one
+    // recovering soonest is used regardless, but paused credentials are never used
+    pub(crate) fn acquire(&self) -> Result<Option<(usize, AuthDetails)>, Error> {
+        if self.keys.is_empty() {
+            return Ok(None);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        self.expire(&mut state, now);
+
+        let start = match self.selection {
+            KeySelection::RoundRobin => state.next,
+            KeySelection::Failover => 0,
+        };
+        let unpaused: Vec<usize> = (0..self.keys.len())
+            .map(|offset| (start + offset) % self.keys.len())
+            .filter(|&index| match self.soft_limit {
+                Some(limit) => state.requests[index].len() < limit,
+                None => true,
+            })
+            .collect();
+
+        let available = unpaused
+            .iter()
+            .copied()
+            .find(|&index| match state.cooldown_until[index] {
+                Some(until) => until <= now,
+                None => true,
+            });
+        let index = match available.or_else(|| {
+            unpaused
+                .iter()
+                .copied()
+                .min_by_key(|&index| state.cooldown_until[index])
+        }) {
+            Some(index) => index,
+            None => {
+                let retry_after = state
+                    .requests
+                    .iter()
+                    .filter_map(|requests| requests.front())
+                    .map(|&sent| (sent + self.window).saturating_duration_since(now))
+                    .min()
+                    .unwrap_or_default();
+                return Err(Error::QuotaExhausted { retry_after });
+            }
+        };
+
+        state.cooldown_until[index] = None;
+        state.next = (index + 1) % self.keys.len();
+        state.requests[index].push_back(now);
+        Ok(Some((index, self.keys[index].clone())))
+    }
+
+    // forgets requests that fell out of the window
+    fn expire(&self, state: &mut PoolState, now: Instant) {
+        for requests in &mut state.requests {
+            while let Some(&sent) = requests.front() {
+                if now.saturating_duration_since(sent) < self.window {
+                    break;
+                }
+                requests.pop_front();
+            }
+        }
+    }
+
+    pub(crate) fn penalize(&self, index: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.cooldown_until[index] = Some(Instant::now() + self.cooldown);
+    }
+}
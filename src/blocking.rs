@@ -0,0 +1,129 @@
+//! Blocking API, for use outside of an async runtime. Requires the `blocking` feature.
+//!
+//! Requests are sent through an internal single-threaded runtime, so scripts and small tools
+//! don't have to set one up themselves.
+//!
+//! ## Example
+//! ```rust
+//! # use gelbooru_api::Error;
+//! # fn example() -> Result<(), Error> {
+//! use gelbooru_api::blocking::{posts, Client};
+//!
+//! let client = Client::public()?;
+//! let query = client.send(posts().tags(&["hatsune_miku"]).limit(5))?;
+//!
+//! for post in &query.posts {
+//!     println!("{}", post.image_url());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::api::{PostQuery, PostsRequestBuilder, TagQuery, TagsRequestBuilder};
+use crate::{AuthDetails, Error};
+use futures::future::LocalBoxFuture;
+use futures::FutureExt;
+use std::future::Future;
+
+pub use crate::{posts, tags};
+
+/// Request that can be sent with a blocking [`Client`](struct.Client.html).
+///
+/// Implemented by the [`posts`](fn.posts.html) and [`tags`](fn.tags.html) request builders.
+pub trait Request: private::Sealed {
+    type Output;
+
+    #[doc(hidden)]
+    fn send<'c>(self, client: &'c crate::Client) -> LocalBoxFuture<'c, Result<Self::Output, Error>>
+    where
+        Self: 'c;
+}
+
+impl<'a> Request for PostsRequestBuilder<'a> {
+    type Output = PostQuery;
+
+    fn send<'c>(self, client: &'c crate::Client) -> LocalBoxFuture<'c, Result<PostQuery, Error>>
+    where
+        Self: 'c,
+    {
+        async move { PostsRequestBuilder::send(&self, client).await }.boxed_local()
+    }
+}
+
+impl Request for TagsRequestBuilder {
+    type Output = TagQuery;
+
+    fn send<'c>(self, client: &'c crate::Client) -> LocalBoxFuture<'c, Result<TagQuery, Error>>
+    where
+        Self: 'c,
+    {
+        TagsRequestBuilder::send(self, client).boxed_local()
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+
+    impl<'a> Sealed for crate::api::PostsRequestBuilder<'a> {}
+    impl Sealed for crate::api::TagsRequestBuilder {}
+}
+
+/// Client blocking the current thread until requests are done.
+///
+/// Must not be used from within an async runtime, as blocking on one from inside another
+/// panics; use the async [`Client`](../struct.Client.html) there instead.
+pub struct Client {
+    client: crate::Client,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl Client {
+    /// Wrap an async client, such as one made with
+    /// [`Client::builder`](../struct.Client.html#method.builder).
+    pub fn new(client: crate::Client) -> Result<Self, Error> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(Self { client, runtime })
+    }
+
+    /// A basic unauthenticated client.
+    ///
+    /// May incur rate-limiting.
+    pub fn public() -> Result<Self, Error> {
+        Self::new(crate::Client::public())
+    }
+
+    /// An authenticated client.
+    pub fn with_auth(details: AuthDetails) -> Result<Self, Error> {
+        Self::new(crate::Client::with_auth(details))
+    }
+
+    /// Send a request, blocking until it's done.
+    pub fn send<R: Request>(&self, request: R) -> Result<R::Output, Error> {
+        self.runtime.block_on(request.send(&self.client))
+    }
+
+    /// Run any future on the client's runtime, for async functionality without a blocking
+    /// counterpart.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::Error;
+    /// # fn example() -> Result<(), Error> {
+    /// use gelbooru_api::blocking::{posts, Client};
+    ///
+    /// let client = Client::public()?;
+    /// let posts = client.block_on(posts().tag("hatsune_miku").fetch_all(client.inner(), 300))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Async client requests are sent with.
+    pub fn inner(&self) -> &crate::Client {
+        &self.client
+    }
+}
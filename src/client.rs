@@ -1,29 +1,348 @@
-use crate::AuthDetails;
+use crate::ratelimit::RateLimiter;
+use crate::transport::{BodyStream, ConnectionSettings, HttpTransport, Resolve};
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+use crate::Proxy;
+use crate::{AuthDetails, AuthPool, Error};
+use futures::StreamExt;
+use http::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH, USER_AGENT};
+use hyper::body::Bytes;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
 
-type HClient = hyper::Client<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>>;
+pub(crate) const DEFAULT_BASE_URL: &str = "https://gelbooru.com";
+const DEFAULT_USER_AGENT: &str = concat!("gelbooru-api/", env!("CARGO_PKG_VERSION"));
+// far above the largest pages the API serves, while still bounding what a broken proxy can send
+const DEFAULT_MAX_RESPONSE_SIZE: usize = 32 * 1024 * 1024;
 
 /// Gelbooru API client.
 /// Used for authentication requests.
 ///
-/// Should generally be reused for multiple requests.
+/// Should generally be reused for multiple requests. Clones are cheap and share the connection
+/// pool, credentials and rate limit, so a client can be handed to every task instead of being
+/// wrapped in an `Arc`.
+#[derive(Clone)]
 pub struct Client {
-    pub(crate) http_client: HClient,
-    pub(crate) auth: Option<AuthDetails>,
+    pub(crate) transport: Arc<dyn HttpTransport>,
+    pub(crate) auth: Option<Arc<AuthPool>>,
+    pub(crate) dump_failed_responses: bool,
+    pub(crate) keep_raw_responses: bool,
+    pub(crate) response_format: ResponseFormat,
+    pub(crate) request_timeout: Option<Duration>,
+    pub(crate) max_response_size: usize,
+    pub(crate) retries: u32,
+    pub(crate) backoff: Duration,
+    pub(crate) rate_limiter: Option<Arc<RateLimiter>>,
+    pub(crate) base_url: Arc<str>,
+    pub(crate) legacy_api: bool,
+    pub(crate) allow_http: bool,
+    pub(crate) headers: Arc<HeaderMap>,
+}
+
+// credentials are masked by `AuthDetails`, and header values are left out as they may hold
+// secrets too
+impl std::fmt::Debug for Client {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Client")
+            .field("base_url", &self.base_url)
+            .field("legacy_api", &self.legacy_api)
+            .field("auth", &self.auth)
+            .field("response_format", &self.response_format)
+            .field("request_timeout", &self.request_timeout)
+            .field("max_response_size", &self.max_response_size)
+            .field("retries", &self.retries)
+            .field("backoff", &self.backoff)
+            .field("rate_limiter", &self.rate_limiter)
+            .field("allow_http", &self.allow_http)
+            .field("headers", &self.headers.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Builder for a [`Client`](struct.Client.html) with connection settings.
+///
+/// See [`Client::builder`](struct.Client.html#method.builder).
+#[derive(Default)]
+pub struct ClientBuilder {
+    auth: Option<AuthPool>,
+    connection: ConnectionSettings,
+    request_timeout: Option<Duration>,
+    max_response_size: Option<usize>,
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    proxy: Option<Proxy>,
+    base_url: Option<String>,
+    legacy_api: bool,
+    allow_http: bool,
+    headers: HeaderMap,
+    transport: Option<Box<dyn HttpTransport>>,
+}
+
+/// Format the API is asked to respond in.
+///
+/// Responses are read into the same types either way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResponseFormat {
+    Json,
+    /// For Gelbooru-based sites with broken JSON output. Requires the `xml` feature.
+    #[cfg(feature = "xml")]
+    Xml,
+}
+
+impl ClientBuilder {
+    /// Authenticate requests.
+    pub fn auth(self, details: AuthDetails) -> Self {
+        self.auth_pool(AuthPool::new(vec![details]))
+    }
+
+    /// Spread requests over several sets of credentials.
+    ///
+    /// See [`AuthPool`](struct.AuthPool.html).
+    pub fn auth_pool(mut self, pool: AuthPool) -> Self {
+        self.auth = Some(pool);
+        self
+    }
+
+    /// Longest to wait for a connection to Gelbooru to be established.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connection.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Longest to wait for a request to complete, from sending it through reading the whole
+    /// response. Requests taking longer fail with
+    /// [`Error::Timeout`](enum.Error.html#variant.Timeout).
+    ///
+    /// Unlike a request's [deadline](api/struct.PostsRequestBuilder.html#method.deadline), this
+    /// applies to every request made, individually.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Largest API response read, in bytes. Larger ones fail with
+    /// [`Error::ResponseTooLarge`](enum.Error.html#variant.ResponseTooLarge) as soon as they
+    /// outgrow it, rather than after being read whole.
+    ///
+    /// Defaults to 32 MiB. File downloads aren't limited.
+    pub fn max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_size = Some(bytes);
+        self
+    }
+
+    /// How long idle connections are kept open to be reused.
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.connection.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Most idle connections kept open per host, unlimited by default.
+    ///
+    /// Crawlers sending many concurrent requests benefit from keeping them around, while
+    /// long-running daemons polling now and then can keep fewer, or none with `0`.
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.connection.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Send TCP keep-alive probes on idle connections at this interval, so connections dropped
+    /// by firewalls or NAT are noticed before being reused. Off by default.
+    pub fn tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.connection.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Connect to `host` at the given address instead of resolving it, such as to reach
+    /// Gelbooru on networks blocking it through DNS. Can be repeated for several addresses.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::Client;
+    /// use std::net::{IpAddr, Ipv4Addr};
+    ///
+    /// let client = Client::builder()
+    ///     .resolve("gelbooru.com", IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)))
+    ///     .build();
+    /// ```
+    pub fn resolve(mut self, host: &str, address: IpAddr) -> Self {
+        self.connection
+            .addresses
+            .entry(host.to_ascii_lowercase())
+            .or_default()
+            .push(address);
+        self
+    }
+
+    /// Resolve host names with a custom resolver, such as through DNS over HTTPS, rather than
+    /// the system's. Addresses given with [`resolve`](#method.resolve) take precedence.
+    pub fn resolver<R: Resolve + 'static>(mut self, resolver: R) -> Self {
+        self.connection.resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Connect through a proxy. Requires the `rustls` or `native-tls` feature.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, Proxy};
+    /// # fn example() -> Result<(), Error> {
+    /// let client = Client::builder()
+    ///     .proxy(Proxy::new("socks5://127.0.0.1:1080")?)
+    ///     .build();
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Site to send requests to, for Gelbooru mirrors and other sites running Gelbooru.
+    /// Defaults to `https://gelbooru.com`.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::Client;
+    /// let client = Client::builder().base_url("https://safebooru.org").build();
+    /// ```
+    pub fn base_url<S: Into<String>>(mut self, url: S) -> Self {
+        self.base_url = Some(url.into());
+        self
+    }
+
+    /// Whether the site runs Gelbooru 0.2, which lists posts without their count and responds
+    /// with nothing at all when there are none.
+    ///
+    /// Without a count, post searches report one more post than seen for as long as pages
+    /// come back full, so paging through them carries on. Set by the presets of sites running
+    /// it, such as [`Client::safebooru`](struct.Client.html#method.safebooru).
+    pub fn legacy_api(mut self, legacy_api: bool) -> Self {
+        self.legacy_api = legacy_api;
+        self
+    }
+
+    /// Allow requests over plain HTTP, for self-hosted sites and test servers without TLS.
+    /// Requests to Gelbooru itself are always made over HTTPS.
+    ///
+    /// Without this, plain HTTP requests fail with
+    /// [`Error::InsecureRequest`](enum.Error.html#variant.InsecureRequest).
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::Client;
+    /// let client = Client::builder()
+    ///     .base_url("http://booru.lan")
+    ///     .allow_http(true)
+    ///     .build();
+    /// ```
+    pub fn allow_http(mut self, allow_http: bool) -> Self {
+        self.allow_http = allow_http;
+        self
+    }
+
+    /// User-Agent sent with every request. Defaults to `gelbooru-api/<version>`.
+    ///
+    /// Bots are expected to identify themselves, and get blocked less for it.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::Client;
+    /// use http::HeaderValue;
+    ///
+    /// let client = Client::builder()
+    ///     .user_agent(HeaderValue::from_static("miku-bot/1.0 (+https://example.com/bot)"))
+    ///     .build();
+    /// ```
+    pub fn user_agent(self, user_agent: HeaderValue) -> Self {
+        self.default_header(USER_AGENT, user_agent)
+    }
+
+    /// Header sent with every request to the API and the site, replacing any set before under
+    /// the same name.
+    ///
+    /// File downloads and webhooks go to other hosts, and are sent without them.
+    pub fn default_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Send requests through another HTTP backend than the built-in one.
+    ///
+    /// The [connection](#method.connect_timeout) [settings](#method.pool_idle_timeout) and
+    /// [proxy](#method.proxy) only apply to the built-in backend, and are ignored with this
+    /// set. See [`transport`](transport/index.html) for an example.
+    pub fn transport<T: HttpTransport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Box::new(transport));
+        self
+    }
+
+    pub fn build(self) -> Client {
+        let transport: Arc<dyn HttpTransport> = match self.transport {
+            Some(transport) => transport.into(),
+            #[cfg(any(feature = "rustls", feature = "native-tls"))]
+            None => Arc::new(crate::transport::HyperTransport::new(
+                &self.connection,
+                self.proxy,
+            )),
+            #[cfg(all(
+                feature = "reqwest",
+                not(any(feature = "rustls", feature = "native-tls"))
+            ))]
+            None => Arc::new(crate::transport::ReqwestTransport::with_settings(
+                &self.connection,
+            )),
+            #[cfg(not(any(feature = "rustls", feature = "native-tls", feature = "reqwest")))]
+            None => Arc::new(crate::transport::NoTransport),
+        };
+
+        let mut headers = self.headers;
+        headers
+            .entry(USER_AGENT)
+            .or_insert(HeaderValue::from_static(DEFAULT_USER_AGENT));
+
+        Client {
+            transport,
+            auth: self.auth.map(Arc::new),
+            dump_failed_responses: false,
+            keep_raw_responses: false,
+            response_format: ResponseFormat::Json,
+            request_timeout: self.request_timeout,
+            max_response_size: self.max_response_size.unwrap_or(DEFAULT_MAX_RESPONSE_SIZE),
+            retries: 0,
+            backoff: Duration::from_millis(250),
+            rate_limiter: None,
+            base_url: match self.base_url {
+                Some(url) => url.trim_end_matches('/').into(),
+                None => DEFAULT_BASE_URL.into(),
+            },
+            legacy_api: self.legacy_api,
+            allow_http: self.allow_http,
+            headers: Arc::new(headers),
+        }
+    }
 }
 
 impl Client {
     fn base() -> Self {
-        let connector = hyper_rustls::HttpsConnectorBuilder::new()
-            .with_native_roots()
-            .https_only()
-            .enable_http1()
-            .build();
-        let http_client = hyper::Client::builder().build::<_, hyper::Body>(connector);
-
-        Self {
-            http_client,
-            auth: None,
-        }
+        Self::builder().build()
+    }
+
+    /// Build a client with custom connection settings, such as timeouts.
+    ///
+    /// Without any, a hung connection makes requests wait forever.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::Client;
+    /// use std::time::Duration;
+    ///
+    /// let client = Client::builder()
+    ///     .connect_timeout(Duration::from_secs(5))
+    ///     .request_timeout(Duration::from_secs(30))
+    ///     .build();
+    /// ```
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
     }
 
     /// A basic unauthenticated client.
@@ -33,13 +352,219 @@ impl Client {
         Self::base()
     }
 
+    /// Client for [Safebooru](https://safebooru.org).
+    pub fn safebooru() -> Self {
+        Self::legacy("https://safebooru.org")
+    }
+
+    /// Client for [Rule 34](https://rule34.xxx).
+    pub fn rule34() -> Self {
+        Self::legacy("https://api.rule34.xxx")
+    }
+
+    /// Client for [TBIB](https://tbib.org).
+    pub fn tbib() -> Self {
+        Self::legacy("https://tbib.org")
+    }
+
+    // sites running Gelbooru 0.2
+    fn legacy(base_url: &str) -> Self {
+        Self::builder().base_url(base_url).legacy_api(true).build()
+    }
+
     /// An authenticated client.
     ///
     /// May incur rate-limiting in extreme cases.
     /// Users that have supported on Patreon have no rate-limiting whatsoever.
     pub fn with_auth(details: AuthDetails) -> Self {
+        Self::with_auth_pool(AuthPool::new(vec![details]))
+    }
+
+    /// An authenticated client spreading requests over several sets of credentials.
+    ///
+    /// See [`AuthPool`](struct.AuthPool.html).
+    pub fn with_auth_pool(pool: AuthPool) -> Self {
         let mut client = Self::base();
-        client.auth = Some(details);
+        client.auth = Some(Arc::new(pool));
         client
     }
+
+    /// Write the body of every response that fails to deserialize to a file in the temporary
+    /// directory, and include its path in the error
+    /// ([`Error::DumpedResponse`](enum.Error.html#variant.DumpedResponse)).
+    ///
+    /// Meant for debugging, and for attaching to bug reports about schema changes.
+    pub fn dump_failed_responses(mut self, enabled: bool) -> Self {
+        self.dump_failed_responses = enabled;
+        self
+    }
+
+    /// Keep the body of every response alongside the parsed
+    /// [`PostQuery`](api/struct.PostQuery.html#method.raw) or
+    /// [`TagQuery`](api/struct.TagQuery.html#method.raw).
+    ///
+    /// Meant for debugging deserialization mismatches, and for reading fields the models don't
+    /// cover yet. Off by default, as it keeps every body in memory for as long as its page.
+    pub fn keep_raw_responses(mut self, enabled: bool) -> Self {
+        self.keep_raw_responses = enabled;
+        self
+    }
+
+    /// Format the API is asked to respond in. Defaults to JSON.
+    pub fn response_format(mut self, format: ResponseFormat) -> Self {
+        self.response_format = format;
+        self
+    }
+
+    /// Retry API requests failing in a [transient](enum.Error.html#method.is_transient) way up
+    /// to `retries` times. Requests aren't retried by default.
+    ///
+    /// Retries happen within a request's
+    /// [deadline](api/struct.PostsRequestBuilder.html#method.deadline), if it has one.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::Client;
+    /// use std::time::Duration;
+    ///
+    /// let client = Client::public()
+    ///     .retries(3)
+    ///     .backoff(Duration::from_millis(250));
+    /// ```
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Wait before the first retry, doubling with every following one. Defaults to 250ms.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Send at most `per_second` requests a second on average, across everything using the
+    /// client. Requests over the limit wait their turn.
+    ///
+    /// Bursts of up to a second's worth of requests are let through at once.
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::Client;
+    /// // one request every two seconds
+    /// let client = Client::public().rate_limit(0.5);
+    /// ```
+    ///
+    /// ## Panics
+    /// If `per_second` isn't positive.
+    pub fn rate_limit(mut self, per_second: f64) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(per_second)));
+        self
+    }
+
+    /// Credentials the client spreads its requests over, if authenticated.
+    ///
+    /// Use [`AuthPool::usage`](struct.AuthPool.html#method.usage) to inspect request quotas.
+    pub fn auth_pool(&self) -> Option<&AuthPool> {
+        self.auth.as_deref()
+    }
+
+    // sends a request and reads its response, within the request timeout
+    pub(crate) async fn send(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> Result<http::Response<Bytes>, Error> {
+        self.admit(request.uri()).await?;
+        self.timed(self.transport.send(request)).await
+    }
+
+    // plain GET whose body is read as it arrives; the timeout only covers receiving the headers,
+    // as large files may rightfully take longer
+    pub(crate) async fn get_streaming(
+        &self,
+        uri: http::Uri,
+    ) -> Result<http::Response<BodyStream>, Error> {
+        let request = http::Request::get(uri)
+            .body(Bytes::new())
+            .expect("GET request is valid");
+        self.admit(request.uri()).await?;
+        self.timed(self.transport.send_streaming(request)).await
+    }
+
+    // fails with `Error::Timeout` past the request timeout
+    async fn timed<T, F>(&self, response: F) -> Result<T, Error>
+    where
+        F: std::future::Future<Output = Result<T, Error>>,
+    {
+        match self.request_timeout {
+            Some(timeout) => crate::runtime::timeout(timeout, response)
+                .await
+                .ok_or(Error::Timeout)?,
+            None => response.await,
+        }
+    }
+
+    // refuses plain HTTP where it isn't allowed, then waits for the rate limiter
+    async fn admit(&self, uri: &http::Uri) -> Result<(), Error> {
+        if uri.scheme() == Some(&http::uri::Scheme::HTTP) {
+            let host = uri.host().unwrap_or_default().to_ascii_lowercase();
+            let gelbooru = host == "gelbooru.com" || host.ends_with(".gelbooru.com");
+            if !self.allow_http || gelbooru {
+                // without the query string, which may hold credentials
+                return Err(Error::InsecureRequest {
+                    url: format!("http://{}{}", host, uri.path()),
+                });
+            }
+        }
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn get(&self, uri: http::Uri) -> Result<http::Response<Bytes>, Error> {
+        let request = http::Request::get(uri)
+            .body(Bytes::new())
+            .expect("GET request is valid");
+        self.send(request).await
+    }
+
+    // GET to the site itself, with the default headers; those may hold secrets, so file hosts
+    // and webhooks never get them
+    pub(crate) async fn get_site(&self, uri: http::Uri) -> Result<http::Response<Bytes>, Error> {
+        let mut request = http::Request::get(uri)
+            .body(Bytes::new())
+            .expect("GET request is valid");
+        for (name, value) in self.headers.iter() {
+            request.headers_mut().insert(name, value.clone());
+        }
+        self.admit(request.uri()).await?;
+        self.timed(self.read_capped(request)).await
+    }
+
+    // reads a response whole, giving up once it outgrows `max_response_size`
+    async fn read_capped(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> Result<http::Response<Bytes>, Error> {
+        let max = self.max_response_size;
+        let (parts, mut chunks) = self.transport.send_streaming(request).await?.into_parts();
+        let declared = parts
+            .headers
+            .get(CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok())
+            .and_then(|length| length.parse::<u64>().ok());
+        if declared.is_some_and(|length| length > max as u64) {
+            return Err(Error::ResponseTooLarge { max });
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk?;
+            if body.len() + chunk.len() > max {
+                return Err(Error::ResponseTooLarge { max });
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(http::Response::from_parts(parts, body.into()))
+    }
 }
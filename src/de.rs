@@ -0,0 +1,248 @@
+//! Deserialization helpers for the quirks of API responses
+
+// the helpers below use visitors rather than untagged enums, which would replace the error of
+// a malformed value with one that doesn't say what was wrong with it
+
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{Error, Expected, IntoDeserializer, MapAccess, SeqAccess, Unexpected, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::fmt::{self, Display};
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+// none for optional values, an error otherwise
+fn none<T, E: Error>(
+    optional: bool,
+    got: Unexpected,
+    expected: &dyn Expected,
+) -> Result<Option<T>, E> {
+    match optional {
+        true => Ok(None),
+        false => Err(E::invalid_type(got, expected)),
+    }
+}
+
+// reads numbers and strings holding them; null and blank strings are none when `optional`
+struct NumberVisitor<T> {
+    optional: bool,
+    number: PhantomData<T>,
+}
+
+impl<T> NumberVisitor<T> {
+    fn new(optional: bool) -> Self {
+        NumberVisitor {
+            optional,
+            number: PhantomData,
+        }
+    }
+}
+
+impl<'de, T> Visitor<'de> for NumberVisitor<T>
+where
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    type Value = Option<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a number, or a string holding one")
+    }
+
+    fn visit_u64<E: Error>(self, value: u64) -> Result<Self::Value, E> {
+        T::deserialize(value.into_deserializer()).map(Some)
+    }
+
+    fn visit_i64<E: Error>(self, value: i64) -> Result<Self::Value, E> {
+        T::deserialize(value.into_deserializer()).map(Some)
+    }
+
+    fn visit_f64<E: Error>(self, value: f64) -> Result<Self::Value, E> {
+        T::deserialize(value.into_deserializer()).map(Some)
+    }
+
+    fn visit_str<E: Error>(self, value: &str) -> Result<Self::Value, E> {
+        match value.trim() {
+            "" => none(self.optional, Unexpected::Str(value), &self),
+            number => number
+                .parse()
+                .map(Some)
+                .map_err(|err| E::custom(format!("invalid number {:?}: {}", value, err))),
+        }
+    }
+
+    fn visit_unit<E: Error>(self) -> Result<Self::Value, E> {
+        none(self.optional, Unexpected::Unit, &self)
+    }
+
+    fn visit_none<E: Error>(self) -> Result<Self::Value, E> {
+        none(self.optional, Unexpected::Option, &self)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+// numbers may be sent as strings, as every value is in XML responses and in those of some
+// Gelbooru-based sites
+pub(crate) fn number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    let number = deserializer.deserialize_any(NumberVisitor::new(false))?;
+    Ok(number.expect("only optional numbers are none"))
+}
+
+// like `number`, with null and empty strings meaning none
+pub(crate) fn optional_number<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    deserializer.deserialize_any(NumberVisitor::new(true))
+}
+
+// like `optional_number`, with 0 meaning none too, as sent for ids of things that don't exist
+pub(crate) fn optional_id<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(optional_number(deserializer)?.filter(|&id| id != 0))
+}
+
+// strings with null and blank strings meaning none, as sent for unset text fields
+pub(crate) fn optional_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<String>::deserialize(deserializer)?.filter(|string| !string.trim().is_empty()))
+}
+
+// like `number`, with null meaning the default, as some Gelbooru-based sites send for zero
+pub(crate) fn number_or_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr + Default,
+    T::Err: Display,
+{
+    Ok(optional_number(deserializer)?.unwrap_or_default())
+}
+
+// flags are 0 or 1, but also show up as booleans, in strings or not; null and blank strings are
+// none when `optional`
+struct FlagVisitor {
+    optional: bool,
+}
+
+impl<'de> Visitor<'de> for FlagVisitor {
+    type Value = Option<u64>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a flag, as a number or boolean")
+    }
+
+    fn visit_bool<E: Error>(self, value: bool) -> Result<Self::Value, E> {
+        Ok(Some(value as u64))
+    }
+
+    fn visit_u64<E: Error>(self, value: u64) -> Result<Self::Value, E> {
+        Ok(Some(value))
+    }
+
+    fn visit_i64<E: Error>(self, value: i64) -> Result<Self::Value, E> {
+        u64::try_from(value)
+            .map(Some)
+            .map_err(|_| E::invalid_value(Unexpected::Signed(value), &self))
+    }
+
+    fn visit_str<E: Error>(self, value: &str) -> Result<Self::Value, E> {
+        match value.trim() {
+            "" => none(self.optional, Unexpected::Str(value), &self),
+            "true" => Ok(Some(1)),
+            "false" => Ok(Some(0)),
+            flag => flag
+                .parse()
+                .map(Some)
+                .map_err(|_| E::invalid_value(Unexpected::Str(value), &self)),
+        }
+    }
+
+    fn visit_unit<E: Error>(self) -> Result<Self::Value, E> {
+        none(self.optional, Unexpected::Unit, &self)
+    }
+
+    fn visit_none<E: Error>(self) -> Result<Self::Value, E> {
+        none(self.optional, Unexpected::Option, &self)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+pub(crate) fn flag<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let flag = deserializer.deserialize_any(FlagVisitor { optional: false })?;
+    Ok(flag.expect("only optional flags are none"))
+}
+
+// like `flag`, with null and empty strings meaning none
+pub(crate) fn optional_flag<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let flag = deserializer.deserialize_any(FlagVisitor { optional: true })?;
+    Ok(flag.map(|flag| flag != 0))
+}
+
+// lists come as an array, a lone object when there's a single item, and null or nothing at all
+// when there are none
+pub(crate) fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct OneOrMany<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for OneOrMany<T> {
+        type Value = Vec<T>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a list, or a single item")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(item) = seq.next_element()? {
+                items.push(item);
+            }
+            Ok(items)
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            T::deserialize(MapAccessDeserializer::new(map)).map(|item| vec![item])
+        }
+
+        fn visit_unit<E: Error>(self) -> Result<Self::Value, E> {
+            Ok(Vec::new())
+        }
+
+        fn visit_none<E: Error>(self) -> Result<Self::Value, E> {
+            Ok(Vec::new())
+        }
+
+        fn visit_some<D: Deserializer<'de>>(
+            self,
+            deserializer: D,
+        ) -> Result<Self::Value, D::Error> {
+            deserializer.deserialize_any(self)
+        }
+    }
+
+    deserializer.deserialize_any(OneOrMany(PhantomData))
+}
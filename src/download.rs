@@ -0,0 +1,533 @@
+//! Downloading post files into organized directory trees
+//!
+//! ## Example
+//! ```rust
+//! # use gelbooru_api::{Client, Error, posts};
+//! # async fn example() -> Result<(), Error> {
+//! use gelbooru_api::download::{Collision, Downloader, Layout, Organizer};
+//!
+//! let client = Client::public();
+//! let organizer = Organizer::new("archive")
+//!     .layout(&[Layout::Copyright, Layout::Artist])
+//!     .collision(Collision::Rename);
+//! let downloader = Downloader::new(organizer);
+//!
+//! let query = posts()
+//!     .tags(&["hatsune_miku"])
+//!     .enriched(&client)
+//!     .await?;
+//!
+//! for post in &query.posts {
+//!     if let Some(path) = downloader.download(&client, post).await? {
+//!         println!("saved {}", path.display());
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::api::{EnrichedPost, Post};
+use crate::{Client, Error};
+use futures::StreamExt;
+use hyper::body::Bytes;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Directory level of an [`Organizer`](struct.Organizer.html)'s tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// First artist tag, or `unknown_artist`.
+    Artist,
+    /// First copyright tag, or `no_copyright`.
+    Copyright,
+    /// Content rating, e.g. `general`.
+    Rating,
+    /// Upload year and month, e.g. `2021/01`.
+    Date,
+}
+
+/// What to do when a file of the same name is already in place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Collision {
+    /// Keep the existing file, and don't download.
+    Skip,
+    Overwrite,
+    /// Save as `name (1).ext`, `name (2).ext`, and so on.
+    Rename,
+}
+
+/// Routes post files into a directory tree.
+///
+/// Files are named after the post's image, which is its MD5 hash.
+#[derive(Clone, Debug)]
+pub struct Organizer {
+    root: PathBuf,
+    layout: Vec<Layout>,
+    collision: Collision,
+}
+
+impl Organizer {
+    /// Organizer saving straight into `root`, skipping existing files.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self {
+            root: root.into(),
+            layout: Vec::new(),
+            collision: Collision::Skip,
+        }
+    }
+
+    /// Directory levels below the root, outermost first.
+    pub fn layout(mut self, layout: &[Layout]) -> Self {
+        self.layout = layout.to_vec();
+        self
+    }
+
+    pub fn collision(mut self, collision: Collision) -> Self {
+        self.collision = collision;
+        self
+    }
+
+    /// Where the post's file belongs, disregarding collisions.
+    pub fn path_for(&self, post: &EnrichedPost) -> PathBuf {
+        let mut path = self.root.clone();
+        for layout in &self.layout {
+            match layout {
+                Layout::Artist => path.push(sanitize(
+                    post.artists().first().unwrap_or(&"unknown_artist"),
+                )),
+                Layout::Copyright => path.push(sanitize(
+                    post.copyrights().first().unwrap_or(&"no_copyright"),
+                )),
+                Layout::Rating => path.push(sanitize(&post.post.rating)),
+                Layout::Date => match upload_month(&post.post) {
+                    Some((year, month)) => {
+                        path.push(year);
+                        path.push(month);
+                    }
+                    None => path.push("unknown_date"),
+                },
+            }
+        }
+        path.push(sanitize(&post.post.image));
+        path
+    }
+
+    /// Write the post's file into the tree, creating directories as needed.
+    ///
+    /// Returns where the file was written, or `None` if skipped due to a collision.
+    pub fn save(&self, post: &EnrichedPost, bytes: &[u8]) -> Result<Option<PathBuf>, Error> {
+        let path = match self.destination(post) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, bytes)?;
+        Ok(Some(path))
+    }
+
+    // final path after handling collisions; `None` when the file should be skipped
+    pub(crate) fn destination(&self, post: &EnrichedPost) -> Option<PathBuf> {
+        let path = self.path_for(post);
+        if !path.exists() {
+            return Some(path);
+        }
+
+        match self.collision {
+            Collision::Skip => None,
+            Collision::Overwrite => Some(path),
+            Collision::Rename => (1..)
+                .map(|n| numbered(&path, n))
+                .find(|path| !path.exists()),
+        }
+    }
+}
+
+/// Downloads post files into an [`Organizer`](struct.Organizer.html)'s tree.
+#[derive(Clone, Debug)]
+pub struct Downloader {
+    organizer: Organizer,
+    embed_metadata: bool,
+    sidecar: bool,
+}
+
+impl Downloader {
+    pub fn new(organizer: Organizer) -> Self {
+        Self {
+            organizer,
+            embed_metadata: false,
+            sidecar: false,
+        }
+    }
+
+    /// Write the post's tags, rating, source and URL into the saved files.
+    ///
+    /// See [`embed_metadata`](fn.embed_metadata.html) for supported formats; other files are saved
+    /// unchanged.
+    pub fn embed_metadata(mut self, embed: bool) -> Self {
+        self.embed_metadata = embed;
+        self
+    }
+
+    /// Write each post's record into a `<filename>.json` file next to it, as gallery-dl does.
+    pub fn sidecar(mut self, sidecar: bool) -> Self {
+        self.sidecar = sidecar;
+        self
+    }
+
+    pub fn organizer(&self) -> &Organizer {
+        &self.organizer
+    }
+
+    /// Download the post's file into the tree.
+    ///
+    /// Files are streamed to disk as they arrive, unless metadata is
+    /// [embedded](#method.embed_metadata), which needs the whole file at hand.
+    ///
+    /// Returns where the file was written, or `None` if skipped due to a collision, in which
+    /// case nothing is downloaded.
+    pub async fn download(
+        &self,
+        client: &Client,
+        post: &EnrichedPost,
+    ) -> Result<Option<PathBuf>, Error> {
+        let path = match self.organizer.destination(post) {
+            Some(path) => path,
+            None => {
+                log_trace!("skipping post {}, file already exists", post.post.id);
+                return Ok(None);
+            }
+        };
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        if self.embed_metadata {
+            let bytes = fetch_file(client, &post.post).await?;
+            let embedded = embed_metadata(post, &bytes);
+            std::fs::write(&path, embedded.as_deref().unwrap_or(&bytes))?;
+        } else {
+            save_file(client, &post.post, &path).await?;
+        }
+
+        log_debug!("saved post {} to {}", post.post.id, path.display());
+        if self.sidecar {
+            write_sidecar(&path, &post.post)?;
+        }
+        Ok(Some(path))
+    }
+
+    // downloads an audited file again, returning `false` if the download doesn't match its MD5;
+    // sidecars hold plain posts, so only the tags the post lists itself are embedded
+    async fn restore(&self, client: &Client, entry: &AuditEntry) -> Result<bool, Error> {
+        let bytes = fetch_file(client, &entry.post).await?;
+        if !matches_md5(&entry.post, &bytes) {
+            return Ok(false);
+        }
+
+        let post = EnrichedPost {
+            post: entry.post.clone(),
+            tags: Vec::new(),
+        };
+        let embedded = match self.embed_metadata {
+            true => embed_metadata(&post, &bytes),
+            false => None,
+        };
+        if let Some(parent) = entry.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&entry.path, embedded.as_deref().unwrap_or(&bytes))?;
+        Ok(true)
+    }
+}
+
+/// Download a post's file.
+pub async fn fetch_file(client: &Client, post: &Post) -> Result<Bytes, Error> {
+    let uri = post.image_url().parse::<hyper::Uri>()?;
+
+    log_debug!("GET {}", uri);
+    let started = crate::runtime::Instant::now();
+    let res = client.get(uri).await?;
+    log_debug!("{} after {:?}", res.status(), started.elapsed());
+    if !res.status().is_success() {
+        return Err(Error::Status(res.status()));
+    }
+
+    Ok(res.into_body())
+}
+
+/// Download a post's file straight to `path`, writing it chunk by chunk as it arrives, so large
+/// files such as videos are never held in memory whole.
+///
+/// The file is written to `<path>.part` first and only moved into place once complete, so an
+/// interrupted download doesn't leave a truncated file behind. Returns the file's size.
+pub async fn save_file(client: &Client, post: &Post, path: &Path) -> Result<u64, Error> {
+    let uri = post.image_url().parse::<hyper::Uri>()?;
+
+    log_debug!("GET {}", uri);
+    let started = crate::runtime::Instant::now();
+    let res = client.get_streaming(uri).await?;
+    log_debug!("{} after {:?}", res.status(), started.elapsed());
+    if !res.status().is_success() {
+        return Err(Error::Status(res.status()));
+    }
+
+    let mut partial = path.to_path_buf().into_os_string();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+
+    let mut body = res.into_body();
+    let written = async {
+        let mut file = std::fs::File::create(&partial)?;
+        let mut written = 0;
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk?;
+            file.write_all(&chunk)?;
+            written += chunk.len() as u64;
+        }
+        file.sync_data()?;
+        Ok::<_, Error>(written)
+    }
+    .await;
+
+    match written {
+        Ok(written) => {
+            std::fs::rename(&partial, path)?;
+            Ok(written)
+        }
+        Err(err) => {
+            let _ = std::fs::remove_file(&partial);
+            Err(err)
+        }
+    }
+}
+
+/// Write the post's record into a `<filename>.json` file next to a downloaded file.
+///
+/// Returns the sidecar's path.
+pub fn write_sidecar(file: &Path, post: &Post) -> Result<PathBuf, Error> {
+    let mut name = file.file_name().unwrap_or_default().to_os_string();
+    name.push(".json");
+    let path = file.with_file_name(name);
+
+    std::fs::write(&path, serde_json::to_vec_pretty(post)?)?;
+    Ok(path)
+}
+
+/// Outcome of an [`audit`](fn.audit.html) of a mirrored directory.
+#[derive(Clone, Debug, Default)]
+pub struct AuditReport {
+    /// Files whose MD5 matches their post.
+    pub verified: Vec<PathBuf>,
+    /// Files whose MD5 doesn't match their post.
+    pub corrupted: Vec<AuditEntry>,
+    /// Sidecars whose file is gone.
+    pub missing: Vec<AuditEntry>,
+    /// Files without a sidecar.
+    pub extra: Vec<PathBuf>,
+    /// Sidecars that couldn't be read as a post.
+    pub unreadable: Vec<PathBuf>,
+    /// Files restored by [`repair`](struct.AuditReport.html#method.repair).
+    pub repaired: Vec<PathBuf>,
+}
+
+/// File of an [`AuditReport`](struct.AuditReport.html) that needs attention.
+#[derive(Clone, Debug)]
+pub struct AuditEntry {
+    pub path: PathBuf,
+    pub post: Post,
+}
+
+impl AuditReport {
+    /// Whether every file was verified, and nothing is missing or out of place.
+    pub fn is_clean(&self) -> bool {
+        self.corrupted.is_empty()
+            && self.missing.is_empty()
+            && self.extra.is_empty()
+            && self.unreadable.is_empty()
+    }
+
+    /// Download corrupted and missing files again, moving them to `repaired`.
+    ///
+    /// Files are saved the way `downloader` saves them, so metadata is embedded again if it
+    /// [embeds it](struct.Downloader.html#method.embed_metadata). Downloads that don't match
+    /// their post's MD5 either are left in place.
+    ///
+    /// A failing entry doesn't stop the others from being repaired: it's kept where it was,
+    /// and its error is returned along with its path.
+    pub async fn repair(
+        &mut self,
+        client: &Client,
+        downloader: &Downloader,
+    ) -> Vec<(PathBuf, Error)> {
+        let mut errors = Vec::new();
+        let corrupted = std::mem::take(&mut self.corrupted);
+        let missing = std::mem::take(&mut self.missing);
+        let entries = corrupted
+            .into_iter()
+            .map(|entry| (entry, false))
+            .chain(missing.into_iter().map(|entry| (entry, true)));
+        for (entry, was_missing) in entries {
+            match downloader.restore(client, &entry).await {
+                Ok(true) => self.repaired.push(entry.path),
+                Ok(false) => self.corrupted.push(entry),
+                Err(error) => {
+                    log_debug!("failed to repair {}: {}", entry.path.display(), error);
+                    errors.push((entry.path.clone(), error));
+                    match was_missing {
+                        true => self.missing.push(entry),
+                        false => self.corrupted.push(entry),
+                    }
+                }
+            }
+        }
+        errors
+    }
+}
+
+/// Verify a mirrored directory against its [sidecars](struct.Downloader.html#method.sidecar).
+///
+/// Files are checked against the MD5 their post's image is named after. Metadata
+/// [embedded](struct.Downloader.html#method.embed_metadata) by the downloader is left out of the
+/// check, so such files verify as well.
+pub fn audit<P: AsRef<Path>>(root: P) -> Result<AuditReport, Error> {
+    let mut files = Vec::new();
+    let mut sidecars = Vec::new();
+    let mut directories = vec![root.as_ref().to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        for entry in std::fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                directories.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                sidecars.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+
+    let mut report = AuditReport::default();
+    let mut indexed = std::collections::HashSet::new();
+    for sidecar in sidecars {
+        let path = sidecar.with_extension("");
+        let post: Post = match std::fs::read(&sidecar)
+            .ok()
+            .and_then(|record| serde_json::from_slice(&record).ok())
+        {
+            Some(post) => post,
+            None => {
+                report.unreadable.push(sidecar);
+                continue;
+            }
+        };
+        indexed.insert(path.clone());
+
+        match std::fs::read(&path) {
+            Ok(bytes) if matches_md5(&post, &bytes) => report.verified.push(path),
+            Ok(_) => report.corrupted.push(AuditEntry { path, post }),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                report.missing.push(AuditEntry { path, post })
+            }
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    report.extra = files
+        .into_iter()
+        .filter(|file| !indexed.contains(file))
+        .collect();
+    Ok(report)
+}
+
+/// Embed the post's tags, rating, source and URL into a downloaded file as an XMP packet.
+///
+/// Tags are written as `dc:subject` keywords, which tools like digiKam index. Only JPEG and PNG
+/// files are supported; `None` is returned for other formats, such as GIFs and videos.
+pub fn embed_metadata(post: &EnrichedPost, bytes: &[u8]) -> Option<Vec<u8>> {
+    crate::xmp::embed(post, bytes)
+}
+
+/// Decode a downloaded file.
+#[cfg(feature = "image")]
+pub fn decode(bytes: &[u8]) -> Result<image::DynamicImage, Error> {
+    Ok(image::load_from_memory(bytes)?)
+}
+
+/// Check that a decoded file matches the post's dimensions, catching truncated or swapped files.
+#[cfg(feature = "image")]
+pub fn verify_dimensions(post: &Post, image: &image::DynamicImage) -> Result<(), Error> {
+    let expected = post.dimensions();
+    let got = (image.width() as u64, image.height() as u64);
+    if expected != got {
+        return Err(Error::DimensionMismatch { expected, got });
+    }
+    Ok(())
+}
+
+/// Shrink an image to fit within `max_size` on both sides, keeping its aspect ratio.
+///
+/// Images already within bounds are returned as is.
+#[cfg(feature = "image")]
+pub fn thumbnail(image: &image::DynamicImage, max_size: u32) -> image::DynamicImage {
+    if image.width() <= max_size && image.height() <= max_size {
+        return image.clone();
+    }
+    image.thumbnail(max_size, max_size)
+}
+
+// post images are named after their MD5, which files with embedded metadata match once it's
+// stripped again
+fn matches_md5(post: &Post, bytes: &[u8]) -> bool {
+    let matches =
+        |bytes: &[u8]| format!("{:x}", md5::compute(bytes)).eq_ignore_ascii_case(post.md5());
+    matches(bytes) || crate::xmp::strip(bytes).is_some_and(|original| matches(&original))
+}
+
+// keeps path segments from escaping their directory or tripping up file systems
+fn sanitize(segment: &str) -> String {
+    let segment: String = segment
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    match segment.trim_matches('.') {
+        "" => "_".to_string(),
+        _ => segment,
+    }
+}
+
+// `name.ext` -> `name (n).ext`
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{} ({}).{}", stem, n, extension.to_string_lossy()),
+        None => format!("{} ({})", stem, n),
+    };
+    path.with_file_name(name)
+}
+
+// year and month of upload from the raw date, e.g. `Sat Jan 02 03:04:05 -0500 2021`
+fn upload_month(post: &Post) -> Option<(String, String)> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let fields: Vec<&str> = post.created_at_raw().split_whitespace().collect();
+    let month = MONTHS
+        .iter()
+        .position(|month| Some(month) == fields.get(1))?;
+    let year = fields
+        .last()
+        .filter(|year| year.chars().all(|c| c.is_ascii_digit()))?;
+    Some((year.to_string(), format!("{:02}", month + 1)))
+}
@@ -6,10 +6,106 @@ pub enum Error {
     ParseAuth,
     #[error("could not parse user id")]
     ParseUserId(std::num::ParseIntError),
+    #[error("all credentials reached their request quota, retry in {retry_after:?}")]
+    QuotaExhausted { retry_after: std::time::Duration },
+    #[error("service unavailable: {kind}")]
+    ServiceUnavailable { kind: UnavailableKind },
+    #[error("invalid tag {tag:?}: {reason}")]
+    InvalidTag { tag: String, reason: &'static str },
+    #[error("invalid query {query:?}: {reason}")]
+    InvalidQuery { query: String, reason: &'static str },
+    #[error("invalid proxy URL {url:?}: {reason}")]
+    InvalidProxy { url: String, reason: &'static str },
+    #[error("invalid search URL {url:?}: {reason}")]
+    InvalidSearchUrl { url: String, reason: &'static str },
+    #[error("too many tags in search: {message}")]
+    TooManyTags { message: String },
+    #[error("search uses a restricted meta-tag: {message}")]
+    RestrictedMetaTag { message: String },
+    #[error("limit of {limit} posts is above the {max} allowed per request")]
+    LimitExceeded { limit: usize, max: usize },
+    #[error("page offset too deep: {message}")]
+    PaginationTooDeep { message: String },
+    #[error("API error: {message}")]
+    Api { message: String },
+    #[error("server responded with unexpected content type {got:?}")]
+    UnexpectedContentType { got: String },
+    #[error("server responded with more than the {max} bytes allowed")]
+    ResponseTooLarge { max: usize },
+    #[error("server responded with an empty body")]
+    EmptyResponse,
+    #[error("server responded with status {0}")]
+    Status(http::StatusCode),
+    #[error("the site only reports how many posts match in XML, which needs the `xml` feature")]
+    CountUnavailable,
+    #[error("expected a single post, got {count}")]
+    MultipleResults { count: usize },
+    #[error("operation deadline exceeded")]
+    DeadlineExceeded,
+    #[error("operation was cancelled")]
+    Cancelled,
+    #[error("refusing to send request to {url:?} over plain HTTP")]
+    InsecureRequest { url: String },
+    #[error("request timed out")]
+    Timeout,
+    #[error("unexpected {field} {value:?} in API response")]
+    UnexpectedValue { field: &'static str, value: String },
+    #[error("watcher sink was closed")]
+    SinkClosed,
     #[error("request error")]
     Request(#[from] hyper::Error),
+    #[error("transport error")]
+    Transport(#[source] Box<dyn std::error::Error + Send + Sync>),
     #[error("an error occured deserializing json response")]
     JsonDeserialize(#[from] serde_json::Error),
+    #[error("failed to deserialize response, body dumped to {path:?}")]
+    DumpedResponse {
+        source: Box<Error>,
+        path: std::path::PathBuf,
+    },
+    #[cfg(feature = "xml")]
+    #[error("an error occured reading xml response")]
+    XmlDeserialize(#[from] quick_xml::Error),
+    #[cfg(feature = "image")]
+    #[error("could not decode image")]
+    ImageDecode(#[from] image::ImageError),
+    #[error("image is {got:?}, but post metadata says {expected:?}")]
+    DimensionMismatch {
+        expected: (u64, u64),
+        got: (u64, u64),
+    },
+    #[error("i/o error")]
+    Io(#[from] std::io::Error),
     #[error("could not parse request Uri")]
     UriParse(#[from] http::uri::InvalidUri),
 }
+
+impl Error {
+    /// Whether the error is likely to go away when retrying, such as a failed connection, a
+    /// timeout, or a server error.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Request(_) | Error::Transport(_) | Error::Timeout => true,
+            Error::Status(status) => status.is_server_error(),
+            _ => false,
+        }
+    }
+}
+
+/// Why Gelbooru served a web page instead of an API response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnavailableKind {
+    /// Cloudflare browser check or captcha.
+    Challenge,
+    /// Maintenance page.
+    Maintenance,
+}
+
+impl std::fmt::Display for UnavailableKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnavailableKind::Challenge => write!(f, "blocked by a Cloudflare challenge"),
+            UnavailableKind::Maintenance => write!(f, "down for maintenance"),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Exporting search results to NDJSON files
+//!
+//! ## Example
+//! ```rust
+//! # use gelbooru_api::{Client, Error, posts};
+//! # async fn example() -> Result<(), Error> {
+//! use gelbooru_api::export::Exporter;
+//!
+//! let client = Client::public();
+//! let progress = Exporter::new(posts().tags(&["hatsune_miku"]), "miku.ndjson")
+//!     .run(&client)
+//!     .await?;
+//!
+//! println!("exported {} posts", progress.written);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::api::PostsRequestBuilder;
+use crate::{Client, Error, Sort};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Writes every post of a search to an NDJSON file, one post per line.
+///
+/// Progress is checkpointed to a state file after every page, so an interrupted export picks up
+/// exactly where it stopped when run again. Posts are exported from newest to oldest, paging by
+/// id rather than page number, so exports aren't limited by Gelbooru's pagination depth.
+#[derive(Clone, Debug)]
+pub struct Exporter<'a> {
+    builder: PostsRequestBuilder<'a>,
+    output: PathBuf,
+    state: PathBuf,
+    page_size: usize,
+}
+
+/// Checkpointed position of an [`Exporter`](struct.Exporter.html).
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExportProgress {
+    /// Id of the last post written.
+    pub last_id: Option<u64>,
+    /// Amount of posts written.
+    pub written: usize,
+    /// Length of the output file at the checkpoint.
+    pub bytes: u64,
+    /// Whether every post was exported.
+    pub complete: bool,
+}
+
+impl<'a> Exporter<'a> {
+    /// Export the posts of a search to `output`, with progress kept in `<output>.state`.
+    ///
+    /// Exports are sorted by id, replacing any sorting set on the search.
+    pub fn new<P: Into<PathBuf>>(builder: PostsRequestBuilder<'a>, output: P) -> Self {
+        let output = output.into();
+        let mut state = output.clone().into_os_string();
+        state.push(".state");
+
+        Self {
+            builder,
+            output,
+            state: state.into(),
+            page_size: 100,
+        }
+    }
+
+    /// Where progress is checkpointed.
+    pub fn state_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.state = path.into();
+        self
+    }
+
+    /// Amount of posts requested per page, and so between checkpoints. At least 1.
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// Checkpointed progress, if the export was started before.
+    pub fn progress(&self) -> Result<Option<ExportProgress>, Error> {
+        match std::fs::read(&self.state) {
+            Ok(state) => Ok(Some(serde_json::from_slice(&state)?)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Export posts, resuming from the checkpoint if there is one.
+    ///
+    /// Anything written to the output after the last checkpoint is discarded before resuming.
+    pub async fn run(&self, client: &Client) -> Result<ExportProgress, Error> {
+        let mut progress = self.progress()?.unwrap_or_default();
+        if progress.complete {
+            return Ok(progress);
+        }
+
+        let mut output = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.output)?;
+        output.set_len(progress.bytes)?;
+
+        loop {
+            let mut builder = self
+                .builder
+                .clone()
+                .sort(Sort::Id)
+                .ascending(false)
+                .limit(self.page_size);
+            if let Some(last_id) = progress.last_id {
+                builder = builder.id_before(last_id);
+            }
+
+            // the limit policy may clamp the page size
+            let page_size = builder.page_size();
+            let query = builder.send(client).await?;
+            let mut page = Vec::new();
+            for post in &query.posts {
+                serde_json::to_writer(&mut page, post)?;
+                page.push(b'\n');
+            }
+            output.write_all(&page)?;
+            output.sync_data()?;
+
+            progress.written += query.posts.len();
+            progress.bytes += page.len() as u64;
+            // blacklisted posts still count as exported, so they aren't requested again
+            let oldest = query
+                .posts
+                .iter()
+                .chain(query.blacklisted())
+                .map(|post| post.id)
+                .min();
+            progress.last_id = oldest.or(progress.last_id);
+            progress.complete = query.received() < page_size;
+            checkpoint(&self.state, &progress)?;
+
+            if progress.complete {
+                return Ok(progress);
+            }
+        }
+    }
+}
+
+// written to a temporary file first, so a crash can't leave a torn state file behind
+fn checkpoint(path: &Path, progress: &ExportProgress) -> Result<(), Error> {
+    let mut temporary = path.to_path_buf().into_os_string();
+    temporary.push(".tmp");
+
+    std::fs::write(&temporary, serde_json::to_vec(progress)?)?;
+    std::fs::rename(&temporary, path)?;
+    Ok(())
+}
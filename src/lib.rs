@@ -1,15 +1,43 @@
+#[cfg(all(
+    target_arch = "wasm32",
+    any(feature = "rustls", feature = "native-tls")
+))]
+compile_error!("the built-in backend isn't available on wasm32, use the `reqwest` feature instead");
+#[cfg(all(target_arch = "wasm32", feature = "blocking"))]
+compile_error!("the `blocking` feature isn't available on wasm32");
+
+#[macro_use]
+mod logging;
 #[cfg(test)]
 mod test;
 
 pub mod api;
 mod auth;
+#[cfg(feature = "blocking")]
+pub mod blocking;
 mod client;
+mod de;
+pub mod download;
 mod error;
-pub use api::{Rating, Ordering, TagType};
-//pub use api::{comments};
-pub use auth::AuthDetails;
-pub use client::Client;
-pub use error::Error;
+pub mod export;
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+mod proxy;
+pub mod query;
+mod ratelimit;
+mod runtime;
+mod stream;
+pub mod transport;
+pub mod watch;
+#[cfg(feature = "xml")]
+mod xml;
+mod xmp;
+pub use api::{FetchOrder, LimitPolicy, Ordering, Rating, Sort, TagType};
+pub use auth::{AuthDetails, AuthPool, KeySelection, KeyUsage};
+pub use client::{Client, ClientBuilder, ResponseFormat};
+pub use error::{Error, UnavailableKind};
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+pub use proxy::Proxy;
+pub use query::Query;
 
 /// Gateway to interacting with the Posts API
 ///
@@ -33,9 +61,18 @@ pub fn posts<'a>() -> api::PostsRequestBuilder<'a> {
     api::PostsRequestBuilder {
         limit: None, // server-side default is 100
         tags: Vec::new(),
+        excluded_tags: Vec::new(),
+        blacklist: Vec::new(),
+        queries: Vec::new(),
         tags_raw: String::new(),
         rating: None,
-        sort_random: false,
+        animated: None,
+        sort: None,
+        ascending: None,
+        pid: None,
+        cid: None,
+        limit_policy: None,
+        options: Default::default(),
     }
 }
 
@@ -57,3 +94,172 @@ pub fn posts<'a>() -> api::PostsRequestBuilder<'a> {
 pub fn tags() -> api::TagsRequestBuilder {
     api::TagsRequestBuilder::new()
 }
+
+/// Gateway to following parent/child relations between posts
+///
+/// Starting from the given post, parents and children are fetched breadth-first until the depth
+/// limit or request budget runs out.
+///
+/// ## Example
+/// ```rust
+/// # use gelbooru_api::{Client, Error, relations};
+/// # async fn example() -> Result<(), Error> {
+/// let client = Client::public();
+///
+/// let graph = relations(6000000)
+///     .max_depth(2)
+///     .max_requests(10)
+///     .send(&client)
+///     .await?;
+///
+/// println!("{} related posts", graph.posts.len());
+/// # Ok(())
+/// # }
+/// ```
+pub fn relations(post_id: u64) -> api::RelationsRequestBuilder {
+    api::RelationsRequestBuilder {
+        post_id,
+        max_depth: 3,
+        max_requests: 20,
+        options: Default::default(),
+    }
+}
+
+/// Begin a comments request. Requires the `xml` feature.
+///
+/// The Comments API only responds in XML, so it's read as such whatever the client's
+/// [response format](struct.Client.html#method.response_format).
+///
+/// ## Example
+/// ```rust
+/// # use gelbooru_api::{Client, Error, comments};
+/// # async fn example() -> Result<(), Error> {
+/// let client = Client::public();
+///
+/// let query = comments().post_id(1).send(&client).await?;
+///
+/// for comment in &query.comments {
+///     println!("{}: {}", comment.creator(), comment.body());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "xml")]
+pub fn comments() -> api::CommentsRequestBuilder {
+    api::CommentsRequestBuilder {
+        post_id: None,
+        options: Default::default(),
+    }
+}
+
+/// Begin a notes request. Requires the `xml` feature.
+///
+/// Like comments, notes are only served as XML.
+///
+/// ## Example
+/// ```rust
+/// # use gelbooru_api::{Client, Error, notes};
+/// # async fn example() -> Result<(), Error> {
+/// let client = Client::public();
+///
+/// let query = notes().post_id(1).send(&client).await?;
+///
+/// for note in &query.notes {
+///     println!("{:?}: {}", note.position(), note.body());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "xml")]
+pub fn notes() -> api::NotesRequestBuilder {
+    api::NotesRequestBuilder {
+        post_id: None,
+        options: Default::default(),
+    }
+}
+
+/// Begin a deleted images request. Requires the `xml` feature.
+///
+/// Useful for pruning local mirrors of posts removed from Gelbooru.
+///
+/// ## Example
+/// ```rust
+/// # use gelbooru_api::{Client, Error, deleted_images};
+/// # async fn example() -> Result<(), Error> {
+/// let client = Client::public();
+///
+/// let query = deleted_images().last_id(9000000).send(&client).await?;
+///
+/// for image in &query.images {
+///     println!("post {} with md5 {} was deleted", image.id(), image.md5());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "xml")]
+pub fn deleted_images() -> api::DeletedImagesRequestBuilder {
+    api::DeletedImagesRequestBuilder {
+        last_id: None,
+        options: Default::default(),
+    }
+}
+
+/// Begin a pools request.
+///
+/// ## Example
+/// ```rust
+/// # use gelbooru_api::{Client, Error, pools};
+/// # async fn example() -> Result<(), Error> {
+/// let client = Client::public();
+///
+/// let pool = pools().get(&client, 1).await?;
+/// println!("{}: {} posts", pool.name(), pool.post_ids().len());
+/// # Ok(())
+/// # }
+/// ```
+pub fn pools() -> api::PoolsRequestBuilder {
+    Default::default()
+}
+
+/// Suggest tags for a partially typed search term, as Gelbooru's search box does.
+///
+/// ## Example
+/// ```rust
+/// # use gelbooru_api::{Client, Error, autocomplete};
+/// # async fn example() -> Result<(), Error> {
+/// let client = Client::public();
+///
+/// for suggestion in autocomplete(&client, "hatsu").await? {
+///     println!("{} ({} posts)", suggestion.label(), suggestion.post_count());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn autocomplete(client: &Client, term: &str) -> Result<Vec<api::Suggestion>, Error> {
+    api::autocomplete(client, term).await
+}
+
+/// Look up the post whose file has the given MD5 hash.
+///
+/// Useful for checking whether a local file is on Gelbooru, or re-associating files with their
+/// posts.
+///
+/// ## Example
+/// ```rust
+/// # use gelbooru_api::{Client, Error, find_by_md5};
+/// # async fn example() -> Result<(), Error> {
+/// let client = Client::public();
+///
+/// if let Some(post) = find_by_md5(&client, "d41d8cd98f00b204e9800998ecf8427e").await? {
+///     println!("found post {}", post.id());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn find_by_md5<S: AsRef<str>>(
+    client: &Client,
+    md5: S,
+) -> Result<Option<api::Post>, Error> {
+    let query = posts().md5(md5).limit(1).send(client).await?;
+    Ok(query.posts.into_iter().next())
+}
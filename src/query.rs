@@ -0,0 +1,358 @@
+//! Typed search queries
+//!
+//! ## Example
+//! ```rust
+//! # use gelbooru_api::{Client, Error, posts};
+//! # async fn example() -> Result<(), Error> {
+//! use gelbooru_api::query::{Comparison, Meta, Query};
+//!
+//! let client = Client::public();
+//! let query = Query::And(vec![
+//!     Query::Or(vec![Query::tag("kagamine_rin"), Query::tag("kagamine_len")]),
+//!     Query::Not(Box::new(Query::tag("comic"))),
+//!     Query::Meta(Meta::Score(Comparison::AtLeast, 10)),
+//! ]);
+//! assert_eq!(query.to_string(), "( kagamine_rin ~ kagamine_len ) -comic score:>=10");
+//!
+//! posts().query(query).send(&client).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Error, Rating, Sort};
+use std::fmt;
+use std::str::FromStr;
+
+/// Search query, as typed into Gelbooru's search box.
+///
+/// [`Display`](#impl-Display-for-Query) writes the query as it would be typed, and parsing
+/// reads it back. Queries are percent-encoded for sending with [`encode`](#method.encode).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Query {
+    Tag(String),
+    /// Posts not matching the query. Queries of more than one term are negated as a group,
+    /// e.g. `-( a b )`.
+    Not(Box<Query>),
+    /// Posts matching any of the queries.
+    Or(Vec<Query>),
+    /// Posts matching all of the queries.
+    And(Vec<Query>),
+    Meta(Meta),
+    /// Sent as is, without encoding.
+    Raw(String),
+}
+
+/// Metatag, filtering or sorting on something other than tags.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Meta {
+    Id(Comparison, u64),
+    Score(Comparison, i64),
+    Width(Comparison, u32),
+    Height(Comparison, u32),
+    /// Upload date, as `YYYY-MM-DD`.
+    Date(Comparison, String),
+    Rating(Rating),
+    /// Uploader's name.
+    User(String),
+    Md5(String),
+    /// Sort order, and whether it's ascending.
+    Sort(Sort, bool),
+}
+
+/// How a [`Meta`](enum.Meta.html) value is compared.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    Less,
+    AtMost,
+    Greater,
+    AtLeast,
+}
+
+impl Query {
+    pub fn tag<S: Into<String>>(tag: S) -> Self {
+        Query::Tag(tag.into())
+    }
+
+    /// Value of the `tags` query string for this query, with everything but raw parts
+    /// percent-encoded.
+    pub fn encode(&self) -> String {
+        self.encode_with("+")
+    }
+
+    // spaces are sent as `+` between tags, but have to be encoded within groups to keep them
+    // a single tag
+    fn encode_with(&self, separator: &str) -> String {
+        use crate::api::encode;
+
+        match self {
+            Query::Tag(tag) => encode(tag),
+            Query::Not(query) if query.is_term() => format!("-{}", query.encode_with(separator)),
+            Query::Not(query) => format!(
+                "-{}{}{}",
+                encode("( "),
+                query.encode_with("%20"),
+                encode(" )")
+            ),
+            Query::Or(queries) if queries.len() == 1 => queries[0].encode_with(separator),
+            Query::Or(queries) => {
+                let alternatives: Vec<String> = queries
+                    .iter()
+                    .map(|query| query.encode_with("%20"))
+                    .collect();
+                format!(
+                    "{}{}{}",
+                    encode("( "),
+                    alternatives.join(&encode(" ~ ")),
+                    encode(" )")
+                )
+            }
+            Query::And(queries) => {
+                let parts: Vec<String> = queries
+                    .iter()
+                    .map(|query| query.encode_with(separator))
+                    .filter(|part| !part.is_empty())
+                    .collect();
+                parts.join(separator)
+            }
+            Query::Meta(meta) => meta.encode(),
+            Query::Raw(raw) => raw.clone(),
+        }
+    }
+
+    // whether the query is a single term, which a `-` can negate without a group
+    fn is_term(&self) -> bool {
+        match self {
+            Query::Tag(_) | Query::Meta(_) => true,
+            Query::Raw(raw) => !raw.contains(|c: char| c.is_whitespace() || c == '+'),
+            Query::Or(queries) if queries.len() == 1 => queries[0].is_term(),
+            // already grouped
+            Query::Or(_) => true,
+            Query::And(queries) => {
+                let mut parts = queries.iter().filter(|query| !query.to_string().is_empty());
+                match (parts.next(), parts.next()) {
+                    (Some(part), None) => part.is_term(),
+                    _ => false,
+                }
+            }
+            // `--a` isn't read as a double negation
+            Query::Not(_) => false,
+        }
+    }
+}
+
+impl Meta {
+    fn encode(&self) -> String {
+        use crate::api::encode;
+
+        match self {
+            Meta::Rating(rating) => format!("rating:{}", rating.metatag()),
+            Meta::User(name) => format!("user:{}", encode(&name.trim().replace(' ', "_"))),
+            Meta::Md5(md5) => format!("md5:{}", encode(&md5.trim().to_lowercase())),
+            Meta::Sort(Sort::Random, _) => "sort:random".to_string(),
+            Meta::Sort(sort, ascending) => {
+                let direction = if *ascending { "asc" } else { "desc" };
+                format!("sort:{}:{}", sort.metatag(), direction)
+            }
+            Meta::Id(comparison, value) => compared("id", *comparison, value, true),
+            Meta::Score(comparison, value) => compared("score", *comparison, value, true),
+            Meta::Width(comparison, value) => compared("width", *comparison, value, true),
+            Meta::Height(comparison, value) => compared("height", *comparison, value, true),
+            Meta::Date(comparison, value) => compared("date", *comparison, value, true),
+        }
+    }
+}
+
+// `name:>=value`, with the operator and value encoded if asked to
+fn compared(name: &str, comparison: Comparison, value: &dyn fmt::Display, encoded: bool) -> String {
+    let operator = match comparison {
+        Comparison::Equal => "",
+        Comparison::Less => "<",
+        Comparison::AtMost => "<=",
+        Comparison::Greater => ">",
+        Comparison::AtLeast => ">=",
+    };
+    match encoded {
+        true => {
+            use crate::api::encode;
+
+            let (operator, value) = (encode(operator), encode(&value.to_string()));
+            format!("{}:{}{}", name, operator, value)
+        }
+        false => format!("{}:{}{}", name, operator, value),
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Query::Tag(tag) | Query::Raw(tag) => write!(f, "{}", tag),
+            Query::Not(query) if query.is_term() => write!(f, "-{}", query),
+            Query::Not(query) => write!(f, "-( {} )", query),
+            Query::Or(queries) if queries.len() == 1 => write!(f, "{}", queries[0]),
+            Query::Or(queries) => {
+                let alternatives: Vec<String> = queries.iter().map(ToString::to_string).collect();
+                write!(f, "( {} )", alternatives.join(" ~ "))
+            }
+            Query::And(queries) => {
+                let parts: Vec<String> = queries
+                    .iter()
+                    .map(ToString::to_string)
+                    .filter(|part| !part.is_empty())
+                    .collect();
+                write!(f, "{}", parts.join(" "))
+            }
+            Query::Meta(meta) => write!(f, "{}", meta),
+        }
+    }
+}
+
+impl fmt::Display for Meta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Meta::Rating(rating) => write!(f, "rating:{}", rating.metatag()),
+            Meta::User(name) => write!(f, "user:{}", name.trim().replace(' ', "_")),
+            Meta::Md5(md5) => write!(f, "md5:{}", md5.trim().to_lowercase()),
+            Meta::Sort(Sort::Random, _) => write!(f, "sort:random"),
+            Meta::Sort(sort, ascending) => {
+                let direction = if *ascending { "asc" } else { "desc" };
+                write!(f, "sort:{}:{}", sort.metatag(), direction)
+            }
+            Meta::Id(comparison, value) => {
+                write!(f, "{}", compared("id", *comparison, value, false))
+            }
+            Meta::Score(comparison, value) => {
+                write!(f, "{}", compared("score", *comparison, value, false))
+            }
+            Meta::Width(comparison, value) => {
+                write!(f, "{}", compared("width", *comparison, value, false))
+            }
+            Meta::Height(comparison, value) => {
+                write!(f, "{}", compared("height", *comparison, value, false))
+            }
+            Meta::Date(comparison, value) => {
+                write!(f, "{}", compared("date", *comparison, value, false))
+            }
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = Error;
+
+    /// Read a query as typed into the search box, e.g. `( a ~ b ) -c score:>=10`.
+    ///
+    /// Metatags that aren't known are read as tags.
+    fn from_str(text: &str) -> Result<Self, Error> {
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        let mut position = 0;
+        let query = parse_and(&tokens, &mut position, false)?;
+        Ok(query)
+    }
+}
+
+// tokens up to the end, or the end of the group when within one
+fn parse_and(tokens: &[&str], position: &mut usize, grouped: bool) -> Result<Query, Error> {
+    let mut parts = Vec::new();
+    while let Some(&token) = tokens.get(*position) {
+        if grouped && (token == "~" || token == ")") {
+            break;
+        }
+        *position += 1;
+
+        let part = match token {
+            "(" => parse_group(tokens, position)?,
+            ")" | "~" => {
+                return Err(invalid_query(
+                    tokens,
+                    "unexpected `)` or `~` outside a group",
+                ))
+            }
+            "-(" => match parse_group(tokens, position)? {
+                Query::Or(mut alternatives) if alternatives.len() == 1 => {
+                    Query::Not(Box::new(alternatives.remove(0)))
+                }
+                group => Query::Not(Box::new(group)),
+            },
+            token => parse_term(token),
+        };
+        parts.push(part);
+    }
+
+    Ok(match parts.len() {
+        1 => parts.remove(0),
+        _ => Query::And(parts),
+    })
+}
+
+// alternatives of a group, its `(` already consumed
+fn parse_group(tokens: &[&str], position: &mut usize) -> Result<Query, Error> {
+    let mut alternatives = vec![parse_and(tokens, position, true)?];
+    loop {
+        match tokens.get(*position) {
+            Some(&"~") => {
+                *position += 1;
+                alternatives.push(parse_and(tokens, position, true)?);
+            }
+            Some(&")") => {
+                *position += 1;
+                return Ok(Query::Or(alternatives));
+            }
+            _ => return Err(invalid_query(tokens, "group isn't closed with `)`")),
+        }
+    }
+}
+
+fn parse_term(token: &str) -> Query {
+    if let Some(negated) = token.strip_prefix('-') {
+        if !negated.is_empty() {
+            return Query::Not(Box::new(parse_term(negated)));
+        }
+    }
+
+    match token
+        .split_once(':')
+        .and_then(|(name, value)| parse_meta(name, value))
+    {
+        Some(meta) => Query::Meta(meta),
+        None => Query::Tag(token.to_string()),
+    }
+}
+
+fn parse_meta(name: &str, value: &str) -> Option<Meta> {
+    let (comparison, operand) = if let Some(operand) = value.strip_prefix(">=") {
+        (Comparison::AtLeast, operand)
+    } else if let Some(operand) = value.strip_prefix("<=") {
+        (Comparison::AtMost, operand)
+    } else if let Some(operand) = value.strip_prefix('>') {
+        (Comparison::Greater, operand)
+    } else if let Some(operand) = value.strip_prefix('<') {
+        (Comparison::Less, operand)
+    } else {
+        (Comparison::Equal, value)
+    };
+
+    Some(match name {
+        "id" => Meta::Id(comparison, operand.parse().ok()?),
+        "score" => Meta::Score(comparison, operand.parse().ok()?),
+        "width" => Meta::Width(comparison, operand.parse().ok()?),
+        "height" => Meta::Height(comparison, operand.parse().ok()?),
+        "date" => Meta::Date(comparison, operand.to_string()),
+        "rating" => Meta::Rating(Rating::from_metatag(value)?),
+        "user" => Meta::User(value.to_string()),
+        "md5" => Meta::Md5(value.to_string()),
+        "sort" => {
+            let (field, direction) = value.split_once(':').unwrap_or((value, "desc"));
+            let sort = Sort::from_metatag(field)?;
+            Meta::Sort(sort, direction == "asc")
+        }
+        _ => return None,
+    })
+}
+
+fn invalid_query(tokens: &[&str], reason: &'static str) -> Error {
+    Error::InvalidQuery {
+        query: tokens.join(" "),
+        reason,
+    }
+}
@@ -0,0 +1,56 @@
+//! Rate limiting shared by every request of a client
+
+use crate::runtime::Instant;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Token bucket refilling at a steady rate, holding up to a second's worth of requests.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(per_second: f64) -> Self {
+        assert!(per_second > 0.0, "rate limit must be positive");
+        let burst = per_second.max(1.0);
+
+        Self {
+            per_second,
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    // tokens are taken before waiting for them, so concurrent requests queue up in order
+    pub(crate) async fn acquire(&self) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            let refilled = now.duration_since(bucket.updated).as_secs_f64() * self.per_second;
+            bucket.tokens = (bucket.tokens + refilled).min(self.burst) - 1.0;
+            bucket.updated = now;
+
+            match bucket.tokens < 0.0 {
+                true => Duration::from_secs_f64(-bucket.tokens / self.per_second),
+                false => Duration::ZERO,
+            }
+        };
+
+        if !wait.is_zero() {
+            log_debug!("rate limited, waiting {:?}", wait);
+            crate::runtime::sleep(wait).await;
+        }
+    }
+}
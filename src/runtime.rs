@@ -0,0 +1,82 @@
+//! Timers, from tokio when it's enabled, and otherwise from `futures-timer`, which runs under
+//! any executor and in the browser
+
+use std::future::Future;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::Instant;
+// `std::time::Instant::now` panics in the browser
+#[cfg(target_arch = "wasm32")]
+pub(crate) use web_time::Instant;
+
+pub(crate) async fn sleep(duration: Duration) {
+    #[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+    tokio::time::sleep(duration).await;
+    #[cfg(not(any(feature = "tokio", target_arch = "wasm32")))]
+    futures_timer::Delay::new(duration).await;
+    #[cfg(target_arch = "wasm32")]
+    AssertSend::new(futures_timer::Delay::new(duration)).await;
+}
+
+/// Run a future for at most `duration`, returning `None` if it took longer.
+#[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    tokio::time::timeout(duration, future).await.ok()
+}
+
+/// Run a future for at most `duration`, returning `None` if it took longer.
+#[cfg(not(all(feature = "tokio", not(target_arch = "wasm32"))))]
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    use futures::future::{select, Either};
+
+    futures::pin_mut!(future);
+    match select(future, Box::pin(sleep(duration))).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
+
+/// Run a future until `deadline`, returning `None` if it wasn't done by then.
+pub(crate) async fn timeout_at<F: Future>(deadline: Instant, future: F) -> Option<F::Output> {
+    timeout(deadline.saturating_duration_since(Instant::now()), future).await
+}
+
+/// Future marked `Send`, for the browser's futures which never are.
+///
+/// Sound as long as there's a single thread, as is the case on `wasm32-unknown-unknown` without
+/// the `atomics` target feature.
+#[cfg(target_arch = "wasm32")]
+pub(crate) struct AssertSend<'a, T>(futures::future::LocalBoxFuture<'a, T>);
+
+// SAFETY: without the `atomics` target feature, wasm32 has no threads for the future to be sent
+// to, so it's only ever polled and dropped on the thread that created it, whatever `T` is. Builds
+// with threads are refused below.
+#[cfg(target_arch = "wasm32")]
+unsafe impl<T> Send for AssertSend<'_, T> {}
+
+#[cfg(all(
+    target_arch = "wasm32",
+    target_feature = "atomics",
+    feature = "reqwest"
+))]
+compile_error!("the reqwest transport doesn't support multi-threaded wasm32 builds");
+
+#[cfg(target_arch = "wasm32")]
+impl<'a, T> AssertSend<'a, T> {
+    pub(crate) fn new<F: Future<Output = T> + 'a>(future: F) -> Self {
+        Self(Box::pin(future))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl<T> Future for AssertSend<'_, T> {
+    type Output = T;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<T> {
+        self.0.as_mut().poll(cx)
+    }
+}
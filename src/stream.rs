@@ -0,0 +1,253 @@
+//! Streams paging through API results
+
+use crate::api::{
+    Post, PostQuery, PostsRequestBuilder, Tag, TagQuery, TagsRequestBuilder, TAG_BATCH_SIZE,
+};
+use crate::Ordering;
+use crate::{Client, Error};
+use futures::future::BoxFuture;
+use futures::stream::{FuturesOrdered, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Stream of every post matching a posts request.
+///
+/// Pages are only requested as the stream is polled, so a slow consumer holds the stream back.
+/// How far ahead it may get is set with [`lookahead`](#method.lookahead), and bounded with
+/// [`max_in_flight`](#method.max_in_flight) and [`max_buffered`](#method.max_buffered).
+///
+/// See [`PostsRequestBuilder::into_stream`](struct.PostsRequestBuilder.html#method.into_stream).
+pub struct PostStream<'a> {
+    client: &'a Client,
+    builder: PostsRequestBuilder<'a>,
+    next_pid: usize,
+    lookahead: usize,
+    max_in_flight: Option<usize>,
+    max_buffered: Option<usize>,
+    exhausted: bool,
+    buffer: VecDeque<Post>,
+    pages: FuturesOrdered<BoxFuture<'a, Result<PostQuery, Error>>>,
+    // held back until the posts buffered before it are handed out
+    error: Option<Error>,
+}
+
+impl<'a> PostStream<'a> {
+    pub(crate) fn new(client: &'a Client, builder: PostsRequestBuilder<'a>) -> Self {
+        Self {
+            client,
+            next_pid: builder.pid.unwrap_or(0),
+            builder,
+            lookahead: 0,
+            max_in_flight: None,
+            max_buffered: None,
+            exhausted: false,
+            buffer: VecDeque::new(),
+            pages: FuturesOrdered::new(),
+            error: None,
+        }
+    }
+
+    /// Amount of pages to request ahead of the one being consumed.
+    ///
+    /// Hides network latency when processing posts takes a while. Defaults to 0, only requesting
+    /// a page once the previous one is used up.
+    pub fn lookahead(mut self, pages: usize) -> Self {
+        self.lookahead = pages;
+        self
+    }
+
+    /// Fetch up to `pages` pages ahead concurrently while the consumer processes posts.
+    ///
+    /// Shorthand for a [`lookahead`](#method.lookahead) of `pages` with as many requests
+    /// [in flight](#method.max_in_flight).
+    ///
+    /// ## Example
+    /// ```rust
+    /// # use gelbooru_api::{Client, Error, posts};
+    /// # async fn example() -> Result<(), Error> {
+    /// use futures::StreamExt;
+    ///
+    /// let client = Client::public();
+    /// let mut stream = posts()
+    ///     .tags(&["step_arts"])
+    ///     .into_stream(&client)
+    ///     .prefetch(4);
+    ///
+    /// while let Some(post) = stream.next().await {
+    ///     println!("{}", post?.id());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn prefetch(self, pages: usize) -> Self {
+        self.lookahead(pages).max_in_flight(pages)
+    }
+
+    /// Maximum amount of page requests pending at once.
+    ///
+    /// Unbounded by default, leaving only the lookahead to decide.
+    pub fn max_in_flight(mut self, pages: usize) -> Self {
+        self.max_in_flight = Some(pages.max(1));
+        self
+    }
+
+    /// Maximum amount of posts held in memory, counting both buffered posts and those of pending
+    /// pages.
+    ///
+    /// No further pages are requested while this would be exceeded, though a single page is
+    /// always allowed so the stream can progress. Unbounded by default.
+    pub fn max_buffered(mut self, posts: usize) -> Self {
+        self.max_buffered = Some(posts);
+        self
+    }
+
+    // keeps `lookahead` pages pending beyond the one being consumed, within the set bounds
+    fn request_pages(&mut self) {
+        let page_size = self.builder.page_size();
+        let mut wanted = if self.buffer.is_empty() {
+            self.lookahead + 1
+        } else {
+            self.lookahead
+        };
+        if let Some(max_in_flight) = self.max_in_flight {
+            wanted = wanted.min(max_in_flight);
+        }
+
+        while !self.exhausted && self.pages.len() < wanted {
+            let held = self.buffer.len() + (self.pages.len() + 1) * page_size;
+            let idle = self.buffer.is_empty() && self.pages.is_empty();
+            if !idle
+                && self
+                    .max_buffered
+                    .is_some_and(|max_buffered| held > max_buffered)
+            {
+                break;
+            }
+
+            let mut builder = self.builder.clone();
+            builder.pid = Some(self.next_pid);
+            self.next_pid += 1;
+            let client = self.client;
+            self.pages
+                .push_back(Box::pin(async move { builder.send(client).await }));
+        }
+    }
+}
+
+impl<'a> Stream for PostStream<'a> {
+    type Item = Result<Post, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            this.request_pages();
+
+            // pending pages are driven on every poll, so prefetching progresses while buffered
+            // posts are handed out
+            while let Poll::Ready(Some(page)) = this.pages.poll_next_unpin(cx) {
+                let page = match page {
+                    Ok(page) => page,
+                    Err(err) => {
+                        this.exhausted = true;
+                        this.pages = FuturesOrdered::new();
+                        this.error = Some(err);
+                        break;
+                    }
+                };
+
+                let seen = page.attributes.offset + page.received();
+                if page.received() == 0 || seen >= page.attributes.count {
+                    // anything requested past the last page would come back empty
+                    this.exhausted = true;
+                    this.pages = FuturesOrdered::new();
+                }
+                this.buffer.extend(page.posts);
+            }
+
+            if let Some(post) = this.buffer.pop_front() {
+                return Poll::Ready(Some(Ok(post)));
+            }
+            if let Some(err) = this.error.take() {
+                return Poll::Ready(Some(Err(err)));
+            }
+            if !this.pages.is_empty() {
+                return Poll::Pending;
+            }
+            if this.exhausted {
+                return Poll::Ready(None);
+            }
+        }
+    }
+}
+
+/// Stream of every tag, in creation order.
+///
+/// See [`TagsRequestBuilder::stream`](struct.TagsRequestBuilder.html#method.stream).
+pub struct TagStream<'a> {
+    client: &'a Client,
+    builder: TagsRequestBuilder,
+    exhausted: bool,
+    buffer: VecDeque<Tag>,
+    page: Option<BoxFuture<'a, Result<TagQuery, Error>>>,
+}
+
+impl<'a> TagStream<'a> {
+    pub(crate) fn new(client: &'a Client, mut builder: TagsRequestBuilder) -> Self {
+        builder.order_by = Some(Ordering::Date);
+        builder.ascending = Some(true);
+        // the API never returns more than a batch, which would pass for the last page
+        builder.limit = Some(builder.limit.unwrap_or(TAG_BATCH_SIZE).min(TAG_BATCH_SIZE));
+
+        Self {
+            client,
+            builder,
+            exhausted: false,
+            buffer: VecDeque::new(),
+            page: None,
+        }
+    }
+}
+
+impl<'a> Stream for TagStream<'a> {
+    type Item = Result<Tag, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(tag) = this.buffer.pop_front() {
+                return Poll::Ready(Some(Ok(tag)));
+            }
+            if this.exhausted {
+                return Poll::Ready(None);
+            }
+
+            let page = this
+                .page
+                .get_or_insert_with(|| Box::pin(this.builder.clone().send(this.client)));
+            let page = match page.as_mut().poll(cx) {
+                Poll::Ready(page) => page,
+                Poll::Pending => return Poll::Pending,
+            };
+            this.page = None;
+
+            let page = match page {
+                Ok(page) => page,
+                Err(err) => {
+                    this.exhausted = true;
+                    return Poll::Ready(Some(Err(err)));
+                }
+            };
+
+            let page_size = this.builder.limit.unwrap_or(TAG_BATCH_SIZE);
+            this.exhausted = page.tags.len() < page_size.max(1);
+            match page.tags.iter().map(|tag| tag.id).max() {
+                Some(last_id) => this.builder.after_id = Some(last_id as usize),
+                None => this.exhausted = true,
+            }
+            this.buffer.extend(page.tags);
+        }
+    }
+}
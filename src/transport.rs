@@ -0,0 +1,405 @@
+//! HTTP backends requests are sent through
+//!
+//! ## Example
+//! ```rust
+//! # use gelbooru_api::{Client, Error};
+//! use futures::future::{BoxFuture, FutureExt};
+//! use gelbooru_api::transport::HttpTransport;
+//! use hyper::body::Bytes;
+//!
+//! // answers every request with an empty page of posts
+//! struct Canned;
+//!
+//! impl HttpTransport for Canned {
+//!     fn send(
+//!         &self,
+//!         _request: http::Request<Bytes>,
+//!     ) -> BoxFuture<'_, Result<http::Response<Bytes>, Error>> {
+//!         let body = Bytes::from_static(br#"{"@attributes":{"limit":100,"offset":0,"count":0}}"#);
+//!         futures::future::ready(Ok(http::Response::new(body))).boxed()
+//!     }
+//! }
+//!
+//! let client = Client::builder().transport(Canned).build();
+//! ```
+
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+use crate::proxy::Connector;
+use crate::Error;
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+use crate::Proxy;
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{BoxStream, StreamExt};
+use hyper::body::Bytes;
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+use hyper::client::connect::dns::{GaiResolver, Name};
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+use hyper::service::Service;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Body of a response read as it arrives, see
+/// [`HttpTransport::send_streaming`](trait.HttpTransport.html#method.send_streaming).
+pub type BodyStream = BoxStream<'static, Result<Bytes, Error>>;
+
+/// Sends requests for a [`Client`](../struct.Client.html), such as to mock responses or to
+/// reuse an application's own HTTP stack.
+///
+/// Responses are read whole, except for downloads. Default headers, rate limiting, timeouts and
+/// retries are taken care of by the client.
+pub trait HttpTransport: Send + Sync {
+    fn send(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> BoxFuture<'_, Result<http::Response<Bytes>, Error>>;
+
+    /// Send a request, handing out the response body chunk by chunk as it arrives, so large
+    /// files can be [written to disk](../download/fn.save_file.html) without being held in
+    /// memory.
+    ///
+    /// Defaults to [`send`](#tymethod.send), with the whole body as a single chunk.
+    fn send_streaming(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> BoxFuture<'_, Result<http::Response<BodyStream>, Error>> {
+        self.send(request)
+            .map(|response| {
+                response.map(|response| {
+                    response.map(|body| futures::stream::once(async { Ok(body) }).boxed())
+                })
+            })
+            .boxed()
+    }
+}
+
+/// Resolves host names to addresses for the built-in transports, such as through DNS over
+/// HTTPS.
+///
+/// See [`ClientBuilder::resolver`](../struct.ClientBuilder.html#method.resolver).
+pub trait Resolve: Send + Sync {
+    fn resolve<'a>(&'a self, host: &'a str) -> BoxFuture<'a, Result<Vec<IpAddr>, Error>>;
+}
+
+// connection settings of `ClientBuilder`, for the built-in transports
+#[derive(Clone, Default)]
+#[cfg_attr(
+    not(any(feature = "rustls", feature = "native-tls", feature = "reqwest")),
+    allow(dead_code)
+)]
+pub(crate) struct ConnectionSettings {
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) pool_idle_timeout: Option<Duration>,
+    pub(crate) pool_max_idle_per_host: Option<usize>,
+    pub(crate) tcp_keepalive: Option<Duration>,
+    // keyed by lowercase host
+    pub(crate) addresses: HashMap<String, Vec<IpAddr>>,
+    pub(crate) resolver: Option<Arc<dyn Resolve>>,
+}
+
+// resolves through the static addresses first, then the custom resolver or the system's
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+#[derive(Clone)]
+pub(crate) struct Resolver {
+    addresses: Arc<HashMap<String, Vec<IpAddr>>>,
+    custom: Option<Arc<dyn Resolve>>,
+    system: GaiResolver,
+}
+
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+impl Resolver {
+    pub(crate) fn new(settings: &ConnectionSettings) -> Self {
+        Self {
+            addresses: Arc::new(settings.addresses.clone()),
+            custom: settings.resolver.clone(),
+            system: GaiResolver::new(),
+        }
+    }
+}
+
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+impl Service<Name> for Resolver {
+    type Response = std::vec::IntoIter<std::net::SocketAddr>;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let addresses = self.addresses.clone();
+        let custom = self.custom.clone();
+        let mut system = self.system.clone();
+
+        async move {
+            // the connector fills in the port
+            let to_socket = |ip| std::net::SocketAddr::new(ip, 0);
+            let host = name.as_str().to_ascii_lowercase();
+            let resolved: Vec<_> = match (addresses.get(&host), custom) {
+                (Some(ips), _) => ips.iter().copied().map(to_socket).collect(),
+                (None, Some(custom)) => custom
+                    .resolve(&host)
+                    .await?
+                    .into_iter()
+                    .map(to_socket)
+                    .collect(),
+                (None, None) => system.call(name).await?.collect(),
+            };
+            Ok(resolved.into_iter())
+        }
+        .boxed()
+    }
+}
+
+// native-tls takes precedence, as rustls is enabled by default
+#[cfg(feature = "native-tls")]
+type HClient = hyper::Client<hyper_tls::HttpsConnector<Connector>>;
+#[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+type HClient = hyper::Client<hyper_rustls::HttpsConnector<Connector>>;
+
+// the default transport
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+pub(crate) struct HyperTransport {
+    http_client: HClient,
+}
+
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+impl HyperTransport {
+    pub(crate) fn new(settings: &ConnectionSettings, proxy: Option<Proxy>) -> Self {
+        let mut http = hyper::client::HttpConnector::new_with_resolver(Resolver::new(settings));
+        http.enforce_http(false);
+        http.set_connect_timeout(settings.connect_timeout);
+        http.set_keepalive(settings.tcp_keepalive);
+        #[cfg(feature = "native-tls")]
+        let connector = hyper_tls::HttpsConnector::new_with_connector(Connector::new(http, proxy));
+        #[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+        let connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .https_or_http()
+            // HTTP/2 is negotiated through ALPN, multiplexing concurrent requests over a single
+            // connection
+            .enable_http1()
+            .enable_http2()
+            .wrap_connector(Connector::new(http, proxy));
+
+        let mut http_client = hyper::Client::builder();
+        if let Some(timeout) = settings.pool_idle_timeout {
+            http_client.pool_idle_timeout(timeout);
+        }
+        if let Some(max) = settings.pool_max_idle_per_host {
+            http_client.pool_max_idle_per_host(max);
+        }
+
+        Self {
+            http_client: http_client.build::<_, hyper::Body>(connector),
+        }
+    }
+}
+
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+impl HttpTransport for HyperTransport {
+    fn send(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> BoxFuture<'_, Result<http::Response<Bytes>, Error>> {
+        async move {
+            let request = request.map(hyper::Body::from);
+            let (parts, body) = self.http_client.request(request).await?.into_parts();
+            let body = hyper::body::to_bytes(body).await?;
+            Ok(http::Response::from_parts(parts, body))
+        }
+        .boxed()
+    }
+
+    fn send_streaming(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> BoxFuture<'_, Result<http::Response<BodyStream>, Error>> {
+        async move {
+            use hyper::body::HttpBody;
+
+            let request = request.map(hyper::Body::from);
+            let response = self.http_client.request(request).await?;
+            Ok(response.map(|body| {
+                futures::stream::unfold(body, |mut body| async move {
+                    let chunk = body.data().await?;
+                    Some((chunk.map_err(Error::from), body))
+                })
+                .boxed()
+            }))
+        }
+        .boxed()
+    }
+}
+
+/// Transport sending requests with [`reqwest`](https://docs.rs/reqwest). Requires the `reqwest`
+/// feature.
+///
+/// Used by default when the `rustls` and `native-tls` features are disabled, so applications already using
+/// `reqwest` don't build a second HTTP stack. On `wasm32`, requests go through the browser's
+/// `fetch`.
+///
+/// ## Example
+/// ```rust
+/// # use gelbooru_api::Client;
+/// use gelbooru_api::transport::ReqwestTransport;
+///
+/// let http = reqwest::Client::new();
+/// let client = Client::builder()
+///     .transport(ReqwestTransport::from(http))
+///     .build();
+/// ```
+#[cfg(feature = "reqwest")]
+#[derive(Clone, Debug, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "reqwest")]
+impl ReqwestTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the browser manages connections itself, so the settings are ignored there
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn with_settings(_: &ConnectionSettings) -> Self {
+        Self::default()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg_attr(any(feature = "rustls", feature = "native-tls"), allow(dead_code))]
+    pub(crate) fn with_settings(settings: &ConnectionSettings) -> Self {
+        let mut client = reqwest::Client::builder()
+            .pool_idle_timeout(settings.pool_idle_timeout)
+            .tcp_keepalive(settings.tcp_keepalive);
+        if let Some(timeout) = settings.connect_timeout {
+            client = client.connect_timeout(timeout);
+        }
+        if let Some(max) = settings.pool_max_idle_per_host {
+            client = client.pool_max_idle_per_host(max);
+        }
+        for (host, ips) in &settings.addresses {
+            let addresses: Vec<_> = ips.iter().map(|ip| (*ip, 0).into()).collect();
+            client = client.resolve_to_addrs(host, &addresses);
+        }
+        if let Some(resolver) = &settings.resolver {
+            client = client.dns_resolver(Arc::new(ReqwestResolver(resolver.clone())));
+        }
+
+        Self {
+            client: client.build().expect("reqwest client is valid"),
+        }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl From<reqwest::Client> for ReqwestTransport {
+    fn from(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl HttpTransport for ReqwestTransport {
+    fn send(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> BoxFuture<'_, Result<http::Response<Bytes>, Error>> {
+        let response = async move {
+            let request = reqwest::Request::try_from(request).map_err(transport_error)?;
+            let response = self
+                .client
+                .execute(request)
+                .await
+                .map_err(transport_error)?;
+
+            let mut converted = http::Response::new(Bytes::new());
+            *converted.status_mut() = response.status();
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                *converted.version_mut() = response.version();
+            }
+            *converted.headers_mut() = response.headers().clone();
+            *converted.body_mut() = response.bytes().await.map_err(transport_error)?;
+            Ok(converted)
+        };
+        // `fetch` futures aren't `Send`
+        #[cfg(target_arch = "wasm32")]
+        let response = crate::runtime::AssertSend::new(response);
+        response.boxed()
+    }
+
+    // the browser's `fetch` bodies can't be read in chunks, so they're left to the default
+    #[cfg(not(target_arch = "wasm32"))]
+    fn send_streaming(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> BoxFuture<'_, Result<http::Response<BodyStream>, Error>> {
+        async move {
+            let request = reqwest::Request::try_from(request).map_err(transport_error)?;
+            let response = self
+                .client
+                .execute(request)
+                .await
+                .map_err(transport_error)?;
+
+            let mut converted = http::Response::new(Bytes::new());
+            *converted.status_mut() = response.status();
+            *converted.version_mut() = response.version();
+            *converted.headers_mut() = response.headers().clone();
+            let chunks = futures::stream::unfold(response, |mut response| async move {
+                match response.chunk().await {
+                    Ok(Some(chunk)) => Some((Ok(chunk), response)),
+                    Ok(None) => None,
+                    Err(error) => Some((Err(transport_error(error)), response)),
+                }
+            });
+            Ok(converted.map(|_| chunks.boxed()))
+        }
+        .boxed()
+    }
+}
+
+// adapts a custom resolver to reqwest's
+#[cfg(all(feature = "reqwest", not(target_arch = "wasm32")))]
+struct ReqwestResolver(Arc<dyn Resolve>);
+
+#[cfg(all(feature = "reqwest", not(target_arch = "wasm32")))]
+impl reqwest::dns::Resolve for ReqwestResolver {
+    fn resolve(&self, name: hyper::client::connect::dns::Name) -> reqwest::dns::Resolving {
+        let resolver = self.0.clone();
+        Box::pin(async move {
+            let ips = resolver.resolve(name.as_str()).await?;
+            let addresses: reqwest::dns::Addrs =
+                Box::new(ips.into_iter().map(|ip| std::net::SocketAddr::new(ip, 0)));
+            Ok(addresses)
+        })
+    }
+}
+
+#[cfg(feature = "reqwest")]
+fn transport_error(error: reqwest::Error) -> Error {
+    Error::Transport(Box::new(error))
+}
+
+// stands in for the built-in transports when none are enabled, until one is set on the builder
+#[cfg(not(any(feature = "rustls", feature = "native-tls", feature = "reqwest")))]
+pub(crate) struct NoTransport;
+
+#[cfg(not(any(feature = "rustls", feature = "native-tls", feature = "reqwest")))]
+impl HttpTransport for NoTransport {
+    fn send(
+        &self,
+        _request: http::Request<Bytes>,
+    ) -> BoxFuture<'_, Result<http::Response<Bytes>, Error>> {
+        let error = "no HTTP backend: enable the `rustls`, `native-tls` or `reqwest` feature, \
+                     or set a transport with `ClientBuilder::transport`";
+        futures::future::ready(Err(Error::Transport(error.into()))).boxed()
+    }
+}
@@ -0,0 +1,207 @@
+//! Watching searches for new posts
+//!
+//! ## Example
+//! ```rust
+//! # use gelbooru_api::{Client, Error, posts};
+//! # async fn example() -> Result<(), Error> {
+//! use gelbooru_api::watch::{Callback, Watcher};
+//! use std::time::Duration;
+//!
+//! let client = Client::public();
+//! Watcher::new(posts().tags(&["hatsune_miku"]))
+//!     .interval(Duration::from_secs(300))
+//!     .sink(Callback(|post: &gelbooru_api::api::Post| println!("new post {}", post.id())))
+//!     .run(&client)
+//!     .await
+//! # }
+//! ```
+
+use crate::api::{Post, PostsRequestBuilder};
+use crate::{Client, Error, Sort};
+use futures::channel::mpsc;
+use futures::future::BoxFuture;
+use futures::{FutureExt, SinkExt};
+use std::time::Duration;
+
+/// Destination of a [`Watcher`](struct.Watcher.html)'s new posts.
+pub trait Sink: Send + Sync {
+    fn deliver<'a>(&'a self, post: &'a Post) -> BoxFuture<'a, Result<(), Error>>;
+}
+
+impl Sink for mpsc::Sender<Post> {
+    fn deliver<'a>(&'a self, post: &'a Post) -> BoxFuture<'a, Result<(), Error>> {
+        let mut sender = self.clone();
+        async move {
+            sender
+                .send(post.clone())
+                .await
+                .map_err(|_| Error::SinkClosed)
+        }
+        .boxed()
+    }
+}
+
+impl Sink for mpsc::UnboundedSender<Post> {
+    fn deliver<'a>(&'a self, post: &'a Post) -> BoxFuture<'a, Result<(), Error>> {
+        let result = self
+            .unbounded_send(post.clone())
+            .map_err(|_| Error::SinkClosed);
+        futures::future::ready(result).boxed()
+    }
+}
+
+/// Sink calling a function with every new post.
+pub struct Callback<F>(pub F);
+
+impl<F: Fn(&Post) + Send + Sync> Sink for Callback<F> {
+    fn deliver<'a>(&'a self, post: &'a Post) -> BoxFuture<'a, Result<(), Error>> {
+        (self.0)(post);
+        futures::future::ready(Ok(())).boxed()
+    }
+}
+
+/// Sink POSTing every new post to a webhook, as a Discord-style embed. Requires the `webhook`
+/// feature.
+#[cfg(feature = "webhook")]
+pub struct Webhook {
+    client: Client,
+    uri: hyper::Uri,
+}
+
+#[cfg(feature = "webhook")]
+impl Webhook {
+    pub fn new(url: &str) -> Result<Self, Error> {
+        Ok(Self {
+            client: Client::builder().allow_http(true).build(),
+            uri: url.parse()?,
+        })
+    }
+
+    /// Body sent for a post.
+    pub fn payload(post: &Post) -> serde_json::Value {
+        serde_json::json!({
+            "embeds": [{
+                "title": format!("Post #{}", post.id()),
+                "url": post.post_url(),
+                "image": { "url": post.image_url() },
+                "footer": { "text": post.tags().join(" ") },
+            }],
+        })
+    }
+}
+
+#[cfg(feature = "webhook")]
+impl Sink for Webhook {
+    fn deliver<'a>(&'a self, post: &'a Post) -> BoxFuture<'a, Result<(), Error>> {
+        async move {
+            let request = hyper::Request::post(self.uri.clone())
+                .header(hyper::header::CONTENT_TYPE, "application/json")
+                .body(hyper::body::Bytes::from(Self::payload(post).to_string()))
+                .expect("webhook request is valid");
+
+            let res = self.client.send(request).await?;
+            if !res.status().is_success() {
+                return Err(Error::Status(res.status()));
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// Polls a search, delivering new posts to sinks as they're uploaded.
+///
+/// The first poll only records the newest post, so posts from before the watcher started aren't
+/// delivered, unless a starting point is given with [`since`](#method.since).
+pub struct Watcher<'a> {
+    builder: PostsRequestBuilder<'a>,
+    interval: Duration,
+    last_id: Option<u64>,
+    sinks: Vec<Box<dyn Sink + 'a>>,
+}
+
+impl<'a> Watcher<'a> {
+    /// Watch a search, polling every minute.
+    pub fn new(builder: PostsRequestBuilder<'a>) -> Self {
+        Self {
+            builder,
+            interval: Duration::from_secs(60),
+            last_id: None,
+            sinks: Vec::new(),
+        }
+    }
+
+    /// Time between polls.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Deliver posts newer than the given post id.
+    pub fn since(mut self, post_id: u64) -> Self {
+        self.last_id = Some(post_id);
+        self
+    }
+
+    /// Add a sink to deliver new posts to.
+    pub fn sink<S: Sink + 'a>(mut self, sink: S) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Id of the newest post seen.
+    pub fn last_id(&self) -> Option<u64> {
+        self.last_id
+    }
+
+    /// Check for new posts once, delivering them oldest first.
+    ///
+    /// Returns the new posts.
+    pub async fn poll(&mut self, client: &Client) -> Result<Vec<Post>, Error> {
+        let last_id = match self.last_id {
+            Some(last_id) => last_id,
+            None => {
+                let newest = self
+                    .builder
+                    .clone()
+                    .sort(Sort::Id)
+                    .ascending(false)
+                    .limit(1)
+                    .send(client)
+                    .await?;
+                self.last_id = Some(newest.first().map_or(0, |post| post.id));
+                return Ok(Vec::new());
+            }
+        };
+
+        // oldest first, so a backlog larger than a page is caught up on over several polls
+        let query = self
+            .builder
+            .clone()
+            .sort(Sort::Id)
+            .ascending(true)
+            .id_after(last_id)
+            .limit(100)
+            .send(client)
+            .await?;
+
+        for post in &query.posts {
+            for sink in &self.sinks {
+                sink.deliver(post).await?;
+            }
+            self.last_id = Some(post.id);
+        }
+        // blacklisted posts are skipped over rather than requested again
+        let newest = query.blacklisted().iter().map(|post| post.id).max();
+        self.last_id = self.last_id.max(newest);
+        Ok(query.posts)
+    }
+
+    /// Poll forever, returning only if polling or delivering fails.
+    pub async fn run(mut self, client: &Client) -> Result<(), Error> {
+        loop {
+            self.poll(client).await?;
+            crate::runtime::sleep(self.interval).await;
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Reading XML responses into the same types as JSON ones
+
+use crate::Error;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde_json::{Map, Value};
+
+// converts a list response such as `<posts count="1"><post><id>1</id></post></posts>` into the
+// shape of its JSON counterpart, `{"@attributes": {"count": "1"}, "post": [{"id": "1"}]}`;
+// fields may be child elements or attributes of the items, and every value is kept a string
+pub(crate) fn list_to_json(body: &[u8], item: &str) -> Result<Value, Error> {
+    let text = String::from_utf8_lossy(body);
+    let mut reader = Reader::from_str(&text);
+    reader.trim_text(true);
+
+    let mut root = Map::new();
+    let mut items = Vec::new();
+    let mut current: Option<Map<String, Value>> = None;
+    let mut field: Option<String> = None;
+    let mut depth = 0;
+
+    loop {
+        let (element, empty) = match reader.read_event()? {
+            Event::Start(element) => (element, false),
+            Event::Empty(element) => (element, true),
+            Event::End(_) => {
+                close(depth, &mut current, &mut field, &mut items);
+                depth -= 1;
+                continue;
+            }
+            Event::Text(text) => {
+                if let (Some(current), Some(field)) = (&mut current, &field) {
+                    let text = text.unescape()?.into_owned();
+                    current.insert(field.clone(), Value::String(text));
+                }
+                continue;
+            }
+            Event::CData(data) => {
+                if let (Some(current), Some(field)) = (&mut current, &field) {
+                    let text = String::from_utf8_lossy(&data.into_inner()).into_owned();
+                    current.insert(field.clone(), Value::String(text));
+                }
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+
+        depth += 1;
+        let name = String::from_utf8_lossy(element.name().as_ref()).into_owned();
+        match depth {
+            1 => root = attributes(&element)?,
+            2 if name == item => current = Some(attributes(&element)?),
+            3 => {
+                if let Some(current) = &mut current {
+                    current.insert(name.clone(), Value::String(String::new()));
+                    field = Some(name);
+                }
+            }
+            _ => {}
+        }
+
+        if empty {
+            close(depth, &mut current, &mut field, &mut items);
+            depth -= 1;
+        }
+    }
+
+    let mut response = Map::new();
+    response.insert("@attributes".to_string(), Value::Object(root));
+    response.insert(item.to_string(), Value::Array(items));
+    Ok(Value::Object(response))
+}
+
+fn close(
+    depth: usize,
+    current: &mut Option<Map<String, Value>>,
+    field: &mut Option<String>,
+    items: &mut Vec<Value>,
+) {
+    match depth {
+        2 => items.extend(current.take().map(Value::Object)),
+        3 => *field = None,
+        _ => {}
+    }
+}
+
+fn attributes(element: &BytesStart) -> Result<Map<String, Value>, Error> {
+    let mut map = Map::new();
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(quick_xml::Error::from)?;
+        let key = String::from_utf8_lossy(attribute.key.as_ref()).into_owned();
+        let value = attribute.unescape_value()?.into_owned();
+        map.insert(key, Value::String(value));
+    }
+    Ok(map)
+}
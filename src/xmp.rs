@@ -0,0 +1,175 @@
+//! Embedding XMP metadata into image files
+
+use crate::api::EnrichedPost;
+
+const XMP_JPEG_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const XMP_PNG_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+// `None` when the format isn't supported or the packet doesn't fit
+pub(crate) fn embed(post: &EnrichedPost, bytes: &[u8]) -> Option<Vec<u8>> {
+    let packet = packet(post);
+    if bytes.starts_with(&[0xff, 0xd8]) {
+        embed_jpeg(bytes, packet.as_bytes())
+    } else if bytes.starts_with(PNG_SIGNATURE) {
+        embed_png(bytes, packet.as_bytes())
+    } else {
+        None
+    }
+}
+
+// file as it was before `embed`, by dropping the packet from where it's inserted; `None` when
+// there's no packet there
+pub(crate) fn strip(bytes: &[u8]) -> Option<Vec<u8>> {
+    if bytes.starts_with(&[0xff, 0xd8]) {
+        strip_jpeg(bytes)
+    } else if bytes.starts_with(PNG_SIGNATURE) {
+        strip_png(bytes)
+    } else {
+        None
+    }
+}
+
+// tags as `dc:subject` keywords, which is what photo managers index
+pub(crate) fn packet(post: &EnrichedPost) -> String {
+    let post_url = post.post.post_url();
+    let subjects: String = post
+        .post
+        .tags()
+        .into_iter()
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| format!("<rdf:li>{}</rdf:li>", escape(tag)))
+        .collect();
+
+    format!(
+        concat!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>",
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">",
+            "<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">",
+            "<rdf:Description rdf:about=\"\"",
+            " xmlns:dc=\"http://purl.org/dc/elements/1.1/\"",
+            " xmlns:gelbooru=\"https://gelbooru.com/ns/1.0/\">",
+            "<dc:subject><rdf:Bag>{subjects}</rdf:Bag></dc:subject>",
+            "<dc:source>{source}</dc:source>",
+            "<dc:identifier>{url}</dc:identifier>",
+            "<gelbooru:id>{id}</gelbooru:id>",
+            "<gelbooru:rating>{rating}</gelbooru:rating>",
+            "</rdf:Description>",
+            "</rdf:RDF>",
+            "</x:xmpmeta>",
+            "<?xpacket end=\"w\"?>",
+        ),
+        subjects = subjects,
+        source = escape(post.post.source().unwrap_or_default()),
+        url = escape(&post_url),
+        id = post.post.id,
+        rating = escape(&post.post.rating),
+    )
+}
+
+// APP1 segment right after SOI and any APP0 (JFIF) segments, which must come first
+fn embed_jpeg(bytes: &[u8], packet: &[u8]) -> Option<Vec<u8>> {
+    let length = 2 + XMP_JPEG_HEADER.len() + packet.len();
+    let length = u16::try_from(length).ok()?;
+
+    let mut offset = 2;
+    while bytes.get(offset..offset + 2) == Some(&[0xff, 0xe0][..]) {
+        let size = u16::from_be_bytes([*bytes.get(offset + 2)?, *bytes.get(offset + 3)?]);
+        offset += 2 + size as usize;
+    }
+    if offset > bytes.len() {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(bytes.len() + 2 + length as usize);
+    out.extend_from_slice(&bytes[..offset]);
+    out.extend_from_slice(&[0xff, 0xe1]);
+    out.extend_from_slice(&length.to_be_bytes());
+    out.extend_from_slice(XMP_JPEG_HEADER);
+    out.extend_from_slice(packet);
+    out.extend_from_slice(&bytes[offset..]);
+    Some(out)
+}
+
+fn strip_jpeg(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut offset = 2;
+    while bytes.get(offset..offset + 2) == Some(&[0xff, 0xe0][..]) {
+        let size = u16::from_be_bytes([*bytes.get(offset + 2)?, *bytes.get(offset + 3)?]);
+        offset += 2 + size as usize;
+    }
+    if bytes.get(offset..offset + 2) != Some(&[0xff, 0xe1][..]) {
+        return None;
+    }
+    let size = u16::from_be_bytes([*bytes.get(offset + 2)?, *bytes.get(offset + 3)?]);
+    let end = offset + 2 + size as usize;
+    let segment = bytes.get(offset + 4..end)?;
+    if !segment.starts_with(XMP_JPEG_HEADER) {
+        return None;
+    }
+
+    Some([&bytes[..offset], &bytes[end..]].concat())
+}
+
+// uncompressed iTXt chunk right after IHDR, which must come first
+fn embed_png(bytes: &[u8], packet: &[u8]) -> Option<Vec<u8>> {
+    let header = PNG_SIGNATURE.len();
+    let ihdr_length = u32::from_be_bytes(bytes.get(header..header + 4)?.try_into().ok()?);
+    // length, type, data and crc
+    let offset = header + 12 + ihdr_length as usize;
+    if offset > bytes.len() {
+        return None;
+    }
+
+    // keyword, compression flag & method, empty language tag and translated keyword
+    let mut chunk = b"iTXt".to_vec();
+    chunk.extend_from_slice(XMP_PNG_KEYWORD);
+    chunk.extend_from_slice(&[0, 0, 0, 0, 0]);
+    chunk.extend_from_slice(packet);
+    let length = u32::try_from(chunk.len() - 4).ok()?;
+
+    let mut out = Vec::with_capacity(bytes.len() + chunk.len() + 8);
+    out.extend_from_slice(&bytes[..offset]);
+    out.extend_from_slice(&length.to_be_bytes());
+    out.extend_from_slice(&chunk);
+    out.extend_from_slice(&crc32(&chunk).to_be_bytes());
+    out.extend_from_slice(&bytes[offset..]);
+    Some(out)
+}
+
+fn strip_png(bytes: &[u8]) -> Option<Vec<u8>> {
+    let header = PNG_SIGNATURE.len();
+    let ihdr_length = u32::from_be_bytes(bytes.get(header..header + 4)?.try_into().ok()?);
+    let offset = header + 12 + ihdr_length as usize;
+    let length = u32::from_be_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?);
+    let chunk = bytes.get(offset + 4..offset + 8 + length as usize)?;
+    if !chunk.starts_with(b"iTXt") || !chunk[4..].starts_with(XMP_PNG_KEYWORD) {
+        return None;
+    }
+
+    // length, type, data and crc
+    let end = offset + 12 + length as usize;
+    Some([&bytes[..offset], bytes.get(end..)?].concat())
+}
+
+// CRC-32 as used by PNG chunks
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}